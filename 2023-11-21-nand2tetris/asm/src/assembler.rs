@@ -0,0 +1,347 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::PathBuf;
+
+use crate::diagnostic::{Diagnostic, Diagnostics, Error};
+use crate::instruction::Instruction;
+use crate::optimizer::optimize;
+use crate::parser::{parse, Kind};
+use crate::preprocessor::{preprocess, Line};
+use crate::symbol_tables::{SymbolKind, SymbolsTable, ROM_SIZE};
+
+/// Where an instruction was written
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Location {
+    /// Empty for the main source, otherwise the included file
+    pub file: String,
+    /// 1-based line in `file`
+    pub line: usize,
+    /// The VM command the instruction implements in code translated by `hvm`, taken from
+    /// the `// push constant 1` comment written before each command
+    pub vm_command: Option<String>,
+    /// The VM function `vm_command` belongs to
+    pub function: Option<String>,
+}
+
+const VM_SEGMENTS: [&str; 8] = [
+    "argument", "local", "static", "constant", "this", "that", "pointer", "temp",
+];
+
+// The VM command of a comment line written by `hvm`, exactly `// push constant 1`: at
+// the start of the line, single spaces and the arguments the command takes. Other
+// comments, such as `// add the two values`, are not VM commands.
+fn vm_comment(text: &str) -> Option<&str> {
+    let command = text.strip_prefix("// ")?;
+    let words: Vec<&str> = command.split(' ').collect();
+    let is_number = |word: &str| !word.is_empty() && word.bytes().all(|b| b.is_ascii_digit());
+    let is_name = |word: &str| {
+        !word.is_empty()
+            && word
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '$' | ':'))
+    };
+    let valid = match words.as_slice() {
+        ["add" | "sub" | "neg" | "eq" | "gt" | "lt" | "and" | "or" | "not" | "return"] => true,
+        ["push" | "pop", segment, index] => VM_SEGMENTS.contains(segment) && is_number(index),
+        ["label" | "goto" | "if-goto", label] => is_name(label),
+        ["function" | "call", name, n] => is_name(name) && is_number(n),
+        _ => false,
+    };
+    valid.then_some(command)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Symbol {
+    pub address: u16,
+    pub kind: SymbolKind,
+}
+
+/// An assembled Hack program
+pub struct Program {
+    /// Instruction words, indexed by ROM address
+    pub words: Vec<u16>,
+    /// Labels and variables defined by the program (predefined symbols are left out)
+    pub symbols: BTreeMap<String, Symbol>,
+    /// Source location of every ROM address
+    pub source_map: Vec<Location>,
+    /// Source line of every ROM address, after preprocessing
+    pub source_lines: Vec<String>,
+}
+
+#[derive(Default)]
+pub struct Options {
+    /// Directory `.include` paths are relative to, the working directory if `None`
+    pub include_dir: Option<PathBuf>,
+    /// Run the peephole optimizer before the first pass
+    pub optimize: bool,
+    /// Reject C-instructions not written as in the book, e.g. `A+D` or `MA=0`
+    pub strict: bool,
+}
+
+pub fn assemble(source: &str) -> Result<Program, Diagnostics> {
+    assemble_with(source, &Options::default())
+}
+
+pub fn assemble_with(source: &str, options: &Options) -> Result<Program, Diagnostics> {
+    let include_dir = options.include_dir.clone().unwrap_or_default();
+    let (lines, mut diagnostics) = preprocess(source, &include_dir);
+    let (instructions, mut symbols_table) = first_pass(&lines, options, &mut diagnostics);
+
+    // Second pass to generate code
+    let mut words: Vec<u16> = Vec::new();
+    let mut source_map: Vec<Location> = Vec::new();
+    let mut source_lines: Vec<String> = Vec::new();
+
+    // Lines are walked alongside the instructions to see the comments between them
+    let mut cursor = 0;
+    let mut vm_command: Option<&str> = None;
+    let mut function: Option<&str> = None;
+    for (line, kind) in &instructions {
+        while !std::ptr::eq(&lines[cursor], *line) {
+            if let Some(command) = vm_comment(&lines[cursor].text) {
+                if let Some(rest) = command.strip_prefix("function ") {
+                    function = rest.split(' ').next();
+                }
+                vm_command = Some(command);
+            }
+            cursor += 1;
+        }
+
+        // A label that is not the current function or one of its `{function}$...`
+        // labels, such as the `$$call` routine of `hvm --shared`, starts code that belongs
+        // to no VM command
+        if let Kind::Label(label) = kind {
+            let label = label.get_label();
+            let inside = function.is_some_and(|function| {
+                label
+                    .strip_prefix(function)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('$'))
+            });
+            if !inside {
+                vm_command = None;
+                function = None;
+            }
+        }
+
+        match kind {
+            Kind::Instruction(ins) => {
+                words.push(match ins {
+                    Instruction::A(a) => match a.resolve(&mut symbols_table) {
+                        Ok(word) => word,
+                        Err(error) => {
+                            let column = line.text.find('@').unwrap();
+                            diagnostics.push(locate(line, error.shift(column)));
+                            0
+                        }
+                    },
+                    Instruction::D(d) => d.resolve(),
+                });
+                source_map.push(Location {
+                    file: line.file.clone(),
+                    line: line.index + 1,
+                    vm_command: vm_command.map(str::to_string),
+                    function: function.map(str::to_string),
+                });
+                source_lines.push(line.text.trim().to_string());
+            }
+            Kind::Label(_) => {}
+        }
+    }
+
+    if !diagnostics.is_empty() {
+        return Err(diagnostics);
+    }
+
+    Ok(Program {
+        words,
+        symbols: symbols_table
+            .user_symbols()
+            .map(|(k, address, kind)| (k.to_string(), Symbol { address, kind }))
+            .collect(),
+        source_map,
+        source_lines,
+    })
+}
+
+impl Program {
+    /// One line per ROM address with the word in binary and hex and the source it came
+    /// from. Labels are shown before the address they refer to.
+    pub fn listing(&self) -> String {
+        let mut labels: BTreeMap<u16, Vec<&str>> = BTreeMap::new();
+        for (name, symbol) in &self.symbols {
+            if symbol.kind == SymbolKind::Label {
+                labels.entry(symbol.address).or_default().push(name);
+            }
+        }
+
+        let mut out = String::from("ROM    binary            hex   source\n");
+        let label_lines = |out: &mut String, address: u16| {
+            for label in labels.get(&address).into_iter().flatten() {
+                writeln!(out, "{:31}({label})", "").unwrap();
+            }
+        };
+        for (address, (word, text)) in self.words.iter().zip(&self.source_lines).enumerate() {
+            label_lines(&mut out, address as u16);
+            writeln!(out, "{address:05}  {word:016b}  {word:04X}  {text}").unwrap();
+        }
+        label_lines(&mut out, self.words.len() as u16);
+        out
+    }
+
+    /// Every label and variable with its address, as read by the disassembler:
+    /// `label NAME ADDRESS` or `variable NAME ADDRESS`
+    pub fn symbol_file(&self) -> String {
+        let mut symbols: Vec<(&String, &Symbol)> = self.symbols.iter().collect();
+        symbols.sort_by_key(|(name, symbol)| {
+            (symbol.kind != SymbolKind::Label, symbol.address, *name)
+        });
+
+        let mut out = String::new();
+        for (name, symbol) in symbols {
+            let kind = match symbol.kind {
+                SymbolKind::Label => "label",
+                _ => "variable",
+            };
+            writeln!(out, "{kind} {name} {}", symbol.address).unwrap();
+        }
+        out
+    }
+
+    /// One line per ROM address with tab-separated fields: the address, the file and
+    /// line of the instruction, then the VM function and command when known. `main`
+    /// names the main source.
+    pub fn source_map_file(&self, main: &str) -> String {
+        let mut out = String::new();
+        for (address, location) in self.source_map.iter().enumerate() {
+            let file = if location.file.is_empty() {
+                main
+            } else {
+                &location.file
+            };
+            writeln!(
+                out,
+                "{address}\t{file}\t{}\t{}\t{}",
+                location.line,
+                location.function.as_deref().unwrap_or_default(),
+                location.vm_command.as_deref().unwrap_or_default()
+            )
+            .unwrap();
+        }
+        out
+    }
+}
+
+/// Read a source map written by `Program::source_map_file`
+pub fn parse_source_map(contents: &str) -> Option<Vec<Location>> {
+    let mut source_map = Vec::new();
+    for (i, line) in contents.lines().enumerate() {
+        let fields: Vec<&str> = line.split('\t').collect();
+        let [address, file, number, function, command] = fields.as_slice() else {
+            return None;
+        };
+        if address.parse::<usize>().ok()? != i {
+            return None;
+        }
+        let optional = |s: &str| (!s.is_empty()).then(|| s.to_string());
+        source_map.push(Location {
+            file: file.to_string(),
+            line: number.parse().ok()?,
+            vm_command: optional(command),
+            function: optional(function),
+        });
+    }
+    Some(source_map)
+}
+
+pub(crate) fn locate(line: &Line, error: Error) -> Diagnostic {
+    let mut diagnostic = Diagnostic::new(line.index, &line.text, error);
+    diagnostic.file = line.file.clone();
+    diagnostic
+}
+
+/// Parse and optionally optimize the preprocessed lines, then give every label its address
+pub(crate) fn first_pass<'a>(
+    lines: &'a [Line],
+    options: &Options,
+    diagnostics: &mut Diagnostics,
+) -> (Vec<(&'a Line, Kind<'a>)>, SymbolsTable) {
+    let mut instructions: Vec<(&Line, Kind)> = Vec::new();
+    for line in lines {
+        match parse(&line.text, options.strict) {
+            Ok(Some(kind)) => instructions.push((line, kind)),
+            Ok(None) => {}
+            Err(error) => diagnostics.push(locate(line, error)),
+        }
+    }
+
+    if options.optimize {
+        instructions = optimize(instructions);
+    }
+
+    let mut symbols_table = SymbolsTable::new();
+
+    // First pass to build symbol table
+    let size = instructions
+        .iter()
+        .filter(|(_, kind)| matches!(kind, Kind::Instruction(_)))
+        .count();
+    let mut line_num: usize = 0;
+    for (line, kind) in &instructions {
+        match kind {
+            Kind::Instruction(_) => {
+                if line_num == ROM_SIZE {
+                    let text = line.text.trim();
+                    let error = Error::new(
+                        line.text.find(text).unwrap(),
+                        text,
+                        format!("ROM overflow: this instruction would be at address {ROM_SIZE}"),
+                    )
+                    .with_suggestion(format!(
+                        "the program has {size} instructions but the ROM holds {ROM_SIZE}"
+                    ));
+                    diagnostics.push(locate(line, error));
+                }
+                line_num += 1;
+            }
+            Kind::Label(name) => {
+                let label = name.get_label();
+                let column = line.text.find(label).unwrap();
+                match symbols_table.kind(label) {
+                    Some(SymbolKind::Predefined) => {
+                        let error = Error::new(
+                            column,
+                            label,
+                            format!(
+                                "label `{label}` collides with the predefined symbol `{label}` = {}",
+                                symbols_table.get(label).unwrap()
+                            ),
+                        )
+                        .with_suggestion("rename the label".to_string());
+                        diagnostics.push(locate(line, error));
+                    }
+                    Some(_) => {
+                        let error =
+                            Error::new(column, label, format!("duplicate symbol `{label}`"));
+                        diagnostics.push(locate(line, error));
+                    }
+                    None if line_num >= ROM_SIZE => {
+                        // `@label` would encode as a C-instruction
+                        let error = Error::new(
+                            column,
+                            label,
+                            format!("ROM overflow: label `{label}` would be at address {line_num}"),
+                        )
+                        .with_suggestion(format!(
+                            "the program has {size} instructions but the ROM holds {ROM_SIZE}"
+                        ));
+                        diagnostics.push(locate(line, error));
+                        symbols_table.insert_label(label.to_string(), line_num as u16);
+                    }
+                    None => symbols_table.insert_label(label.to_string(), line_num as u16),
+                }
+            }
+        }
+    }
+
+    (instructions, symbols_table)
+}
@@ -0,0 +1,59 @@
+use std::env;
+use std::fs;
+use std::process::ExitCode;
+
+use anyhow::{bail, Context, Result};
+
+use asm::formatter::{format, Options};
+
+const USAGE: &str = "[--check] [--addresses] file...";
+
+// Rewrite Hack assembly files in place in the canonical style. With `--check`,
+// only list the files that are not formatted and fail if there are any.
+fn main() -> Result<ExitCode> {
+    let arguments: Vec<String> = env::args().collect();
+    let usage = || {
+        format!(
+            "Usage: {} {USAGE}",
+            arguments.first().unwrap_or(&"asmfmt".to_string())
+        )
+    };
+
+    let mut check = false;
+    let mut options = Options::default();
+    let mut files = Vec::new();
+    for arg in arguments.iter().skip(1) {
+        match arg.as_str() {
+            "--check" => check = true,
+            "--addresses" => options.addresses = true,
+            _ if arg.starts_with("--") => bail!(usage()),
+            _ => files.push(arg),
+        }
+    }
+    if files.is_empty() {
+        bail!(usage());
+    }
+
+    let mut unformatted = false;
+    for file in files {
+        let contents =
+            fs::read_to_string(file).with_context(|| format!("Failed to read {file}"))?;
+        let formatted = format(&contents, &options).map_err(|d| d.in_file(file))?;
+        if formatted == contents {
+            continue;
+        }
+
+        if check {
+            println!("{file} is not formatted");
+            unformatted = true;
+        } else {
+            fs::write(file, formatted).with_context(|| format!("Failed to write {file}"))?;
+        }
+    }
+
+    Ok(if unformatted {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
@@ -0,0 +1,59 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+
+use asm::disassembler::{disassemble, SymbolFile};
+use asm::hack;
+
+// Usage: dis [--le] file
+//
+// `file` is either a `.hack` file or a raw image of 16-bit big-endian words
+// (little-endian with `--le`). The assembly is written to stdout. Names are
+// restored from `file` with a `.sym` extension when it exists.
+fn main() -> Result<()> {
+    let arguments: Vec<String> = env::args().collect();
+    let (little_endian, file) = match arguments.as_slice() {
+        [_, file] => (false, file),
+        [_, flag, file] if flag == "--le" => (true, file),
+        _ => bail!(
+            "Usage: {} [--le] file",
+            arguments.first().unwrap_or(&"dis".to_string())
+        ),
+    };
+
+    let path = Path::new(file);
+    let contents = fs::read(path).with_context(|| format!("Failed to read {file}"))?;
+    let words = if path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("hack"))
+    {
+        hack::parse(&String::from_utf8(contents).context("A .hack file must be text")?)?
+    } else {
+        if contents.len() % 2 != 0 {
+            bail!("Raw image has an odd number of bytes");
+        }
+        contents
+            .chunks(2)
+            .map(|b| match little_endian {
+                true => u16::from_le_bytes([b[0], b[1]]),
+                false => u16::from_be_bytes([b[0], b[1]]),
+            })
+            .collect()
+    };
+
+    let sym_path = path.with_extension("sym");
+    let symbols = if sym_path.exists() {
+        let contents = fs::read_to_string(&sym_path)
+            .with_context(|| format!("Failed to read {}", sym_path.display()))?;
+        SymbolFile::parse(&contents)
+            .with_context(|| format!("Malformed symbol file {}", sym_path.display()))?
+    } else {
+        SymbolFile::default()
+    };
+
+    print!("{}", disassemble(&words, &symbols)?);
+
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+
+use asm::object::{link, Object};
+use asm::output::Format;
+
+const USAGE: &str = "[--format hack|raw-le|raw-be|ihex|logisim|c|rust] -o out file.hobj...";
+
+// Link objects written by `asm -c` into a program. Objects are placed in ROM in
+// the order given, so the one with the entry point comes first.
+fn main() -> Result<()> {
+    let arguments: Vec<String> = env::args().collect();
+    let usage = || {
+        format!(
+            "Usage: {} {USAGE}",
+            arguments.first().unwrap_or(&"hld".to_string())
+        )
+    };
+
+    let mut format = Format::Hack;
+    let mut out = None;
+    let mut files = Vec::new();
+    let mut args = arguments.iter().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => {
+                let name = args.next().with_context(usage)?;
+                format = name.parse().map_err(anyhow::Error::msg)?;
+            }
+            "-o" => out = Some(args.next().with_context(usage)?),
+            _ => files.push(arg),
+        }
+    }
+    let Some(out) = out else {
+        bail!(usage());
+    };
+    if files.is_empty() {
+        bail!(usage());
+    }
+
+    let mut objects = Vec::new();
+    for file in files {
+        let contents =
+            fs::read_to_string(file).with_context(|| format!("Failed to read {file}"))?;
+        let object = Object::parse(&contents)
+            .map_err(anyhow::Error::msg)
+            .with_context(|| format!("Malformed object file {file}"))?;
+        objects.push((file.clone(), object));
+    }
+
+    let words = link(&objects)?;
+
+    let path = Path::new(out);
+    let name = path.file_stem().unwrap_or_default().to_string_lossy();
+    fs::write(path, format.encode(&words, &name))
+        .with_context(|| format!("Failed to write {out}"))?;
+
+    Ok(())
+}
@@ -0,0 +1,177 @@
+use std::fmt;
+
+/// An error found on a single line. `column` is a 0-based byte offset into the line.
+pub struct Error {
+    pub column: usize,
+    pub text: String,
+    pub message: String,
+    pub suggestion: Option<String>,
+}
+
+impl Error {
+    pub fn new(column: usize, text: &str, message: String) -> Error {
+        Error {
+            column,
+            text: text.to_string(),
+            message,
+            suggestion: None,
+        }
+    }
+
+    pub fn with_suggestion(mut self, suggestion: String) -> Error {
+        self.suggestion = Some(suggestion);
+        self
+    }
+
+    // Errors are created relative to the instruction; the parser moves them to the line
+    pub fn shift(mut self, n: usize) -> Error {
+        self.column += n;
+        self
+    }
+}
+
+/// An `Error` located in a source file. `file` is empty until the caller names it.
+pub struct Diagnostic {
+    pub file: String,
+    pub line: usize,
+    pub source: String,
+    pub error: Error,
+}
+
+impl Diagnostic {
+    pub fn new(line_index: usize, source: &str, error: Error) -> Diagnostic {
+        Diagnostic {
+            file: String::new(),
+            line: line_index + 1,
+            source: source.to_string(),
+            error,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Diagnostic {
+            file,
+            line,
+            source,
+            error,
+        } = self;
+
+        let file = if file.is_empty() { "<source>" } else { file };
+        writeln!(
+            f,
+            "{file}:{line}:{}: error: {}",
+            error.column + 1,
+            error.message
+        )?;
+        writeln!(f, "    {}", source.trim_end())?;
+        write!(
+            f,
+            "    {}{}",
+            " ".repeat(error.column),
+            "^".repeat(error.text.chars().count().max(1))
+        )?;
+        if let Some(suggestion) = &error.suggestion {
+            write!(f, "\n    help: {suggestion}")?;
+        }
+        Ok(())
+    }
+}
+
+/// All the errors found in a run, reported together.
+#[derive(Default)]
+pub struct Diagnostics(Vec<Diagnostic>);
+
+impl Diagnostics {
+    pub fn new() -> Diagnostics {
+        Diagnostics(Vec::new())
+    }
+
+    pub fn push(&mut self, diagnostic: Diagnostic) {
+        self.0.push(diagnostic);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.0.iter()
+    }
+
+    /// Name the main source. Diagnostics in included files keep their file.
+    pub fn in_file(mut self, file: &str) -> Diagnostics {
+        for diagnostic in self.0.iter_mut().filter(|d| d.file.is_empty()) {
+            diagnostic.file = file.to_string();
+        }
+        self
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut diagnostics: Vec<&Diagnostic> = self.0.iter().collect();
+        diagnostics.sort_by_key(|d| (&d.file, d.line));
+        for diagnostic in diagnostics {
+            writeln!(f, "{diagnostic}\n")?;
+        }
+        match self.0.len() {
+            1 => write!(f, "aborting due to 1 error"),
+            n => write!(f, "aborting due to {n} errors"),
+        }
+    }
+}
+
+impl fmt::Debug for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl std::error::Error for Diagnostics {}
+
+/// Find the candidate closest to `word`. A candidate using exactly the same
+/// characters (e.g. `A+D` for `D+A`) wins, otherwise the one with the smallest
+/// edit distance, as long as it is not a complete rewrite.
+pub fn nearest<'a>(
+    word: &str,
+    candidates: impl Iterator<Item = &'a str> + Clone,
+) -> Option<&'a str> {
+    let sorted = |s: &str| {
+        let mut chars: Vec<char> = s.chars().collect();
+        chars.sort_unstable();
+        chars
+    };
+    let word_sorted = sorted(word);
+    if let Some(c) = candidates.clone().find(|c| sorted(c) == word_sorted) {
+        return Some(c);
+    }
+
+    candidates
+        .map(|c| (edit_distance(word, c), c))
+        .filter(|(distance, c)| *distance < c.len().max(word.len()).max(2))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, c)| c)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitute.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+
+    row[b.len()]
+}
@@ -0,0 +1,195 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use crate::instruction::{COMPS, JUMPS};
+
+/// A word that no Hack instruction encodes to
+pub struct InvalidWord {
+    pub address: usize,
+    pub word: u16,
+}
+
+impl fmt::Display for InvalidWord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "ROM[{}] = {:016b} is not a valid Hack instruction",
+            self.address, self.word
+        )
+    }
+}
+
+impl fmt::Debug for InvalidWord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl std::error::Error for InvalidWord {}
+
+/// Names read from a `.sym` file. Each line is `label NAME ADDRESS` or `variable NAME ADDRESS`.
+/// An address may have several names, e.g. two labels in a row, kept in file order.
+#[derive(Default)]
+pub struct SymbolFile {
+    pub labels: BTreeMap<u16, Vec<String>>,
+    pub variables: BTreeMap<u16, Vec<String>>,
+}
+
+impl SymbolFile {
+    pub fn parse(contents: &str) -> Option<SymbolFile> {
+        let mut symbols = SymbolFile::default();
+        for line in contents.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            let mut fields = line.split_whitespace();
+            let (kind, name, address) = (fields.next()?, fields.next()?, fields.next()?);
+            let address = address.parse::<u16>().ok()?;
+            let names = match kind {
+                "label" => symbols.labels.entry(address).or_default(),
+                "variable" => symbols.variables.entry(address).or_default(),
+                _ => return None,
+            };
+            names.push(name.to_string());
+        }
+        Some(symbols)
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        self.labels
+            .values()
+            .chain(self.variables.values())
+            .flatten()
+            .any(|n| n == name)
+    }
+}
+
+/// Mnemonic of a `dest` field, in the order used by the book
+pub fn dest_mnemonic(d: u16) -> &'static str {
+    ["", "M", "D", "MD", "A", "AM", "AD", "AMD"][d as usize & 0b111]
+}
+
+pub fn comp_mnemonic(c: u16) -> Option<&'static str> {
+    COMPS
+        .iter()
+        .find(|(_, bits)| *bits == c & 0b111_1111)
+        .map(|(mnemonic, _)| *mnemonic)
+}
+
+pub fn jump_mnemonic(j: u16) -> Option<&'static str> {
+    JUMPS
+        .iter()
+        .find(|(_, bits)| *bits == j & 0b111)
+        .map(|(mnemonic, _)| *mnemonic)
+}
+
+/// Whether `word` is a C-instruction. The assembler always sets bits 13 and 14, so a
+/// word with one of them clear does not come from any instruction.
+pub fn is_c_instruction(word: u16) -> bool {
+    word & 0xE000 == 0xE000
+}
+
+/// Decode a single word, e.g. `@5` or `AM=M-1;JGT`
+pub fn decode(word: u16) -> Option<String> {
+    if word & 0x8000 == 0 {
+        return Some(format!("@{word}"));
+    }
+    if !is_c_instruction(word) {
+        return None;
+    }
+
+    let comp = comp_mnemonic(word >> 6)?;
+    let mut s = String::new();
+    let dest = dest_mnemonic(word >> 3);
+    if !dest.is_empty() {
+        s.push_str(dest);
+        s.push('=');
+    }
+    s.push_str(comp);
+    if let Some(jump) = jump_mnemonic(word) {
+        s.push(';');
+        s.push_str(jump);
+    }
+    Some(s)
+}
+
+/// Whether a C-instruction may jump
+pub fn is_jump(word: u16) -> bool {
+    is_c_instruction(word) && word & 0b111 != 0
+}
+
+/// Whether a C-instruction reads or writes M
+pub fn uses_memory(word: u16) -> bool {
+    is_c_instruction(word) && (word & 0x1000 != 0 || word & 0b1000 != 0)
+}
+
+/// Turn ROM words back into assembly.
+///
+/// Every `@addr` directly followed by a jump marks `addr` as a jump target and gets
+/// a label, named from `symbols` when possible and `L{addr}` otherwise, with a suffix
+/// if `symbols` already uses that name. Other labels in `symbols`, including every
+/// alias of an address, are emitted where they were defined, and `@addr` followed by
+/// an instruction accessing `M` uses the variable name at `addr`, if there is one.
+pub fn disassemble(words: &[u16], symbols: &SymbolFile) -> Result<String, InvalidWord> {
+    let mnemonics = words
+        .iter()
+        .enumerate()
+        .map(|(address, &word)| decode(word).ok_or(InvalidWord { address, word }))
+        .collect::<Result<Vec<String>, InvalidWord>>()?;
+
+    let targets: BTreeSet<u16> = words
+        .windows(2)
+        .filter(|w| w[0] & 0x8000 == 0 && is_jump(w[1]))
+        .map(|w| w[0])
+        .filter(|&target| target as usize <= words.len())
+        .collect();
+
+    let mut labels: BTreeMap<u16, Vec<String>> = symbols
+        .labels
+        .iter()
+        .filter(|(&address, _)| address as usize <= words.len())
+        .map(|(&address, names)| (address, names.clone()))
+        .collect();
+    for &target in &targets {
+        labels.entry(target).or_insert_with(|| {
+            // A name of the symbol file may already be spelled `L{target}`
+            let name = (0..)
+                .map(|n| match n {
+                    0 => format!("L{target}"),
+                    n => format!("L{target}_{n}"),
+                })
+                .find(|name| !symbols.contains(name))
+                .unwrap();
+            vec![name]
+        });
+    }
+
+    let mut out = String::new();
+    for (address, mnemonic) in mnemonics.iter().enumerate() {
+        for label in labels.get(&(address as u16)).into_iter().flatten() {
+            out.push_str(&format!("({label})\n"));
+        }
+
+        let word = words[address];
+        let next = words.get(address + 1).copied().unwrap_or(0);
+        let name = if word & 0x8000 != 0 {
+            None
+        } else if is_jump(next) {
+            labels.get(&word).and_then(|names| names.first())
+        } else if uses_memory(next) {
+            symbols.variables.get(&word).and_then(|names| names.first())
+        } else {
+            None
+        };
+
+        match name {
+            Some(name) => out.push_str(&format!("@{name}\n")),
+            None => {
+                out.push_str(mnemonic);
+                out.push('\n');
+            }
+        }
+    }
+    for label in labels.get(&(words.len() as u16)).into_iter().flatten() {
+        out.push_str(&format!("({label})\n"));
+    }
+
+    Ok(out)
+}
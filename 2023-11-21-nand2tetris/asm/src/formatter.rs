@@ -0,0 +1,236 @@
+use std::collections::HashSet;
+
+use crate::diagnostic::{Diagnostic, Diagnostics, Error};
+use crate::disassembler::decode;
+use crate::instruction::Instruction;
+use crate::parser::{parse, Kind};
+
+const INDENT: usize = 4;
+
+#[derive(Default)]
+pub struct Options {
+    /// Start the trailing comment of every instruction with its ROM address, e.g. `// [12]`
+    pub addresses: bool,
+}
+
+enum Line {
+    Blank,
+    Comment(String),
+    Code {
+        indent: usize,
+        code: String,
+        comment: Option<String>,
+        instruction: bool,
+    },
+}
+
+/// Rewrite `source` in the canonical style:
+///
+/// - labels start at column 0 and everything else is indented under them
+/// - C-instructions are spelled as the disassembler prints them, e.g. `MD=D+1;JGT`
+/// - trailing comments of a paragraph (lines between blank lines) are aligned
+/// - runs of blank lines become one blank line
+///
+/// Preprocessor directives and macro bodies are kept as written, apart from their
+/// indentation. Address comments need a file without directives, since those can
+/// change the addresses.
+pub fn format(source: &str, options: &Options) -> Result<String, Diagnostics> {
+    let mut diagnostics = Diagnostics::new();
+
+    let macros: HashSet<&str> = source
+        .lines()
+        .filter_map(|line| split(line).0.strip_prefix(".macro"))
+        .filter_map(|rest| rest.split_whitespace().next())
+        .collect();
+
+    let mut lines: Vec<Line> = Vec::new();
+    let mut in_macro = false;
+    let mut directive = None;
+    for (index, raw) in source.lines().enumerate() {
+        let (code, comment) = split(raw);
+        if code.is_empty() {
+            lines.push(match comment {
+                Some(comment) => Line::Comment(comment),
+                None => Line::Blank,
+            });
+            continue;
+        }
+
+        let first_word = code.split_whitespace().next().unwrap_or_default();
+        let (indent, code, instruction) = if code.starts_with('.') {
+            directive.get_or_insert((index, raw));
+            match first_word {
+                ".macro" => in_macro = true,
+                ".endm" => in_macro = false,
+                _ => {}
+            }
+            (0, code.to_string(), false)
+        } else if in_macro || macros.contains(first_word) {
+            (INDENT, code.to_string(), false)
+        } else {
+            match parse(raw, false) {
+                Ok(Some(Kind::Label(label))) => (0, format!("({})", label.get_label()), false),
+                Ok(Some(Kind::Instruction(Instruction::A(a)))) => {
+                    (INDENT, format!("@{}", a.value()), true)
+                }
+                Ok(Some(Kind::Instruction(Instruction::D(d)))) => {
+                    (INDENT, decode(d.resolve()).unwrap(), true)
+                }
+                Ok(None) => unreachable!("the line has code"),
+                Err(error) => {
+                    diagnostics.push(Diagnostic::new(index, raw, error));
+                    continue;
+                }
+            }
+        };
+        lines.push(Line::Code {
+            indent,
+            code,
+            comment,
+            instruction,
+        });
+    }
+
+    if options.addresses {
+        if let Some((index, raw)) = directive {
+            let (code, _) = split(raw);
+            let error = Error::new(
+                raw.find(code).unwrap_or(0),
+                code,
+                "address comments need a file without preprocessor directives".to_string(),
+            );
+            diagnostics.push(Diagnostic::new(index, raw, error));
+        }
+    }
+    if !diagnostics.is_empty() {
+        return Err(diagnostics);
+    }
+
+    // Address markers are recomputed on every run with `addresses`, and comments are
+    // left alone otherwise
+    let mut address = 0;
+    for line in &mut lines {
+        if let Line::Code {
+            comment,
+            instruction: true,
+            ..
+        } = line
+        {
+            *comment = if options.addresses {
+                let text = comment.take().map(|c| strip_address(&c).to_string());
+                match text.filter(|t| !t.is_empty()) {
+                    Some(text) => Some(format!("[{address}] {text}")),
+                    None => Some(format!("[{address}]")),
+                }
+            } else {
+                comment.take().filter(|t| !t.is_empty())
+            };
+            address += 1;
+        }
+    }
+
+    Ok(render(&lines))
+}
+
+// Code without surrounding whitespace, and the trailing comment without `//`
+fn split(line: &str) -> (&str, Option<String>) {
+    match line.split_once("//") {
+        Some((code, comment)) => (code.trim(), Some(comment.trim().to_string())),
+        None => (line.trim(), None),
+    }
+}
+
+// `[12] text` becomes `text`
+fn strip_address(comment: &str) -> &str {
+    comment
+        .strip_prefix('[')
+        .and_then(|rest| rest.split_once(']'))
+        .filter(|(n, _)| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+        .map_or(comment, |(_, rest)| rest.trim_start())
+}
+
+fn render(lines: &[Line]) -> String {
+    let mut out = String::new();
+    let mut previous_blank = true;
+    let mut i = 0;
+    while i < lines.len() {
+        // A paragraph ends at a blank line
+        let end = lines[i..]
+            .iter()
+            .position(|l| matches!(l, Line::Blank))
+            .map_or(lines.len(), |p| i + p);
+        if end == i {
+            if !previous_blank {
+                out.push('\n');
+            }
+            previous_blank = true;
+            i += 1;
+            continue;
+        }
+
+        let paragraph = &lines[i..end];
+        let column = paragraph
+            .iter()
+            .filter_map(|line| match line {
+                Line::Code {
+                    indent,
+                    code,
+                    comment: Some(_),
+                    ..
+                } => Some(indent + code.len()),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0);
+
+        for (j, line) in paragraph.iter().enumerate() {
+            match line {
+                Line::Comment(comment) => {
+                    // Full-line comments are indented like the code they precede, and
+                    // start at column 0 when they end the paragraph
+                    let indent = paragraph[j..]
+                        .iter()
+                        .find_map(|l| match l {
+                            Line::Code { indent, .. } => Some(*indent),
+                            _ => None,
+                        })
+                        .unwrap_or(0);
+                    out.push_str(&" ".repeat(indent));
+                    out.push_str(&comment_text(comment));
+                }
+                Line::Code {
+                    indent,
+                    code,
+                    comment,
+                    ..
+                } => {
+                    out.push_str(&" ".repeat(*indent));
+                    out.push_str(code);
+                    if let Some(comment) = comment {
+                        let padding = column - indent - code.len() + 2;
+                        out.push_str(&" ".repeat(padding));
+                        out.push_str(&comment_text(comment));
+                    }
+                }
+                Line::Blank => unreachable!(),
+            }
+            out.push('\n');
+        }
+        previous_blank = false;
+        i = end;
+    }
+
+    // No trailing blank line
+    while out.ends_with("\n\n") {
+        out.pop();
+    }
+    out
+}
+
+fn comment_text(comment: &str) -> String {
+    if comment.is_empty() || comment.starts_with('/') {
+        format!("//{comment}")
+    } else {
+        format!("// {comment}")
+    }
+}
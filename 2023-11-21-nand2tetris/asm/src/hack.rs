@@ -0,0 +1,18 @@
+use anyhow::{bail, Context, Result};
+
+/// Read the words of a `.hack` file, one 16-character binary number per line
+pub fn parse(contents: &str) -> Result<Vec<u16>> {
+    contents
+        .lines()
+        .map(str::trim)
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(index, line)| {
+            if line.len() != 16 {
+                bail!("Line {} is not a 16-bit word: {line}", index + 1);
+            }
+            u16::from_str_radix(line, 2)
+                .with_context(|| format!("Line {} is not a 16-bit word: {line}", index + 1))
+        })
+        .collect()
+}
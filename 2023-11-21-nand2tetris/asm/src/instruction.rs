@@ -1,125 +1,275 @@
-use crate::symbol_tables::SymbolsTable;
+use crate::diagnostic::{nearest, Error};
+use crate::symbol_tables::{is_valid_symbol, SymbolsTable, VARIABLE_END};
+
+/// `a` bit followed by `c1..c6` for every comp mnemonic
+pub const COMPS: [(&str, u16); 28] = [
+    ("0", 0b010_1010),
+    ("1", 0b011_1111),
+    ("-1", 0b011_1010),
+    ("D", 0b000_1100),
+    ("A", 0b011_0000),
+    ("M", 0b111_0000),
+    ("!D", 0b000_1101),
+    ("!A", 0b011_0001),
+    ("!M", 0b111_0001),
+    ("-D", 0b000_1111),
+    ("-A", 0b011_0011),
+    ("-M", 0b111_0011),
+    ("D+1", 0b001_1111),
+    ("A+1", 0b011_0111),
+    ("M+1", 0b111_0111),
+    ("D-1", 0b000_1110),
+    ("A-1", 0b011_0010),
+    ("M-1", 0b111_0010),
+    ("D+A", 0b000_0010),
+    ("D+M", 0b100_0010),
+    ("D-A", 0b001_0011),
+    ("D-M", 0b101_0011),
+    ("A-D", 0b000_0111),
+    ("M-D", 0b100_0111),
+    ("D&A", 0b000_0000),
+    ("D&M", 0b100_0000),
+    ("D|A", 0b001_0101),
+    ("D|M", 0b101_0101),
+];
+
+pub const JUMPS: [(&str, u16); 7] = [
+    ("JGT", 0b001),
+    ("JEQ", 0b010),
+    ("JGE", 0b011),
+    ("JLT", 0b100),
+    ("JNE", 0b101),
+    ("JLE", 0b110),
+    ("JMP", 0b111),
+];
 
 pub struct A<'a>(&'a str);
-pub struct D<'a>(&'a str);
+pub struct D(u16);
 
 pub enum Instruction<'a> {
     A(A<'a>),
-    D(D<'a>),
+    D(D),
+}
+
+// Byte offset of `inner` in `outer`. `inner` must be a subslice of `outer`.
+fn offset(outer: &str, inner: &str) -> usize {
+    inner.as_ptr() as usize - outer.as_ptr() as usize
 }
 
 impl A<'_> {
-    pub fn from(s: &str) -> A {
-        A(s)
+    pub fn parse(s: &str) -> Result<A<'_>, Error> {
+        let value = &s[1..];
+
+        if value.starts_with(|c: char| c.is_ascii_digit()) {
+            let num = value.parse::<u32>().map_err(|_| {
+                Error::new(1, value, format!("invalid constant `{value}`"))
+                    .with_suggestion("constants must be decimal numbers".to_string())
+            })?;
+            if num > 32767 {
+                let error = Error::new(
+                    1,
+                    value,
+                    format!("constant `{value}` does not fit in 15 bits"),
+                );
+                return Err(if num <= 0xFFFF {
+                    error.with_suggestion(format!(
+                        "load the complement with `@{}` followed by `D=!A`",
+                        !num & 0xFFFF
+                    ))
+                } else {
+                    error.with_suggestion("the largest constant is 32767".to_string())
+                });
+            }
+            return Ok(A(s));
+        }
+
+        if !is_valid_symbol(value) {
+            return Err(Error::new(1, value, format!("invalid symbol `{value}`")).with_suggestion(
+                "symbols are made of letters, digits, `_`, `.`, `$` and `:` and do not begin with a digit"
+                    .to_string(),
+            ));
+        }
+
+        Ok(A(s))
     }
 
-    pub fn resolve(&self, symbols_table: &mut SymbolsTable) -> u16 {
+    /// The constant or symbol after `@`
+    pub fn value(&self) -> &str {
+        &self.0[1..]
+    }
+
+    pub fn resolve(&self, symbols_table: &mut SymbolsTable) -> Result<u16, Error> {
         let value = &self.0[1..];
 
         if let Ok(num) = value.parse::<u16>() {
-            if num <= 32767 {
-                return num;
-            }
-            unreachable!();
+            return Ok(num);
         }
 
-        // Assume valid symbol now
-        // Symbols: A symbol can be any sequence of letters, digits,
-        // underscore (_), dot (.), dollar sign ($), and colon (:) that does not begin with a digit.
-
         // Refer to Label
         if let Some(num) = symbols_table.get(value) {
-            if *num <= 32767 {
-                return *num;
-            }
-            unreachable!();
+            return Ok(*num);
         }
 
         // Refer to Variable
-        symbols_table.insert_variable(value.to_string());
-        let num = symbols_table.get(value).unwrap();
-        if *num <= 32767 {
-            return *num;
+        let address = symbols_table.insert_variable(value.to_string());
+        if address >= VARIABLE_END {
+            let region = if address < 24576 {
+                "the screen"
+            } else {
+                "the keyboard"
+            };
+            return Err(Error::new(
+                1,
+                value,
+                format!("variable `{value}` would be allocated at RAM[{address}], inside {region} memory map"),
+            )
+            .with_suggestion(format!(
+                "at most {} variables fit in RAM[16..{}]",
+                VARIABLE_END - 16,
+                VARIABLE_END - 1
+            )));
         }
-        unreachable!();
+        Ok(address)
     }
 }
 
-impl D<'_> {
-    pub fn from(s: &str) -> D {
-        D(s)
+// Comp mnemonic of `comp` written with spaces or with the operands of `+`, `&` or `|`
+// swapped, e.g. `A + D` for `D+A`
+fn normalize_comp(comp: &str) -> Option<&'static str> {
+    let comp: String = comp.chars().filter(|c| !c.is_whitespace()).collect();
+    let lookup = |comp: &str| COMPS.iter().find(|(m, _)| *m == comp).map(|(m, _)| *m);
+    lookup(&comp).or_else(|| {
+        let (i, op) = comp
+            .char_indices()
+            .skip(1)
+            .find(|(_, c)| "+&|".contains(*c))?;
+        lookup(&format!("{}{op}{}", &comp[i + 1..], &comp[..i]))
+    })
+}
+
+// The bit of a destination letter, 0 if it is not one
+fn dest_bit(c: char) -> u16 {
+    match c {
+        'M' => 0b001,
+        'D' => 0b010,
+        'A' => 0b100,
+        _ => 0,
     }
+}
 
-    // Assume valid D-instruction
-    pub fn resolve(&self) -> u16 {
+impl D {
+    /// Parse a C-instruction. Unless `strict`, the comp may contain spaces and the operands
+    /// of `+`, `&` and `|` may come in either order, and the dest letters may come in any
+    /// order. In strict mode only the spellings of the book are accepted, from either
+    /// edition: `MD` or `DM`, `AMD` or `ADM`.
+    pub fn parse(s: &str, strict: bool) -> Result<D, Error> {
         let mut d: u16 = 0;
         let mut j: u16 = 0;
 
-        let remaining = if let Some((dest, other)) = self.0.split_once('=') {
+        let remaining = if let Some((dest, other)) = s.split_once('=') {
             let dest = dest.trim_end();
-            if dest.contains('M') {
-                d |= 0b001;
+            if dest.is_empty() {
+                return Err(Error::new(
+                    0,
+                    "=",
+                    "missing destination before `=`".to_string(),
+                ));
             }
-            if dest.contains('D') {
-                d |= 0b010;
+            for (i, c) in dest.char_indices() {
+                let bit = match dest_bit(c) {
+                    0 => {
+                        return Err(Error::new(0, dest, format!("invalid destination `{dest}`"))
+                            .with_suggestion(
+                                "a destination is a combination of `A`, `D` and `M`".to_string(),
+                            ))
+                    }
+                    bit => bit,
+                };
+                if d & bit != 0 {
+                    return Err(Error::new(
+                        i,
+                        &dest[i..i + 1],
+                        format!("`{c}` appears twice in destination `{dest}`"),
+                    ));
+                }
+                d |= bit;
             }
-            if dest.contains('A') {
-                d |= 0b100;
+            // The first edition of the book orders destination letters as `AMD`, the
+            // second as `ADM`
+            let spell = |order: &str| -> String {
+                order.chars().filter(|c| d & dest_bit(*c) != 0).collect()
+            };
+            let canonical = spell("AMD");
+            if strict && dest != canonical && dest != spell("ADM") {
+                return Err(Error::new(
+                    0,
+                    dest,
+                    format!("destination `{dest}` is not in canonical order"),
+                )
+                .with_suggestion(format!("write `{canonical}`")));
             }
             other.trim_start()
         } else {
-            self.0
+            s
         };
 
         let comp = if let Some((comp, jump)) = remaining.split_once(';') {
-            j = match jump.trim_start() {
-                "JGT" => 0b001,
-                "JEQ" => 0b010,
-                "JGE" => 0b011,
-                "JLT" => 0b100,
-                "JNE" => 0b101,
-                "JLE" => 0b110,
-                "JMP" => 0b111,
-                _ => unreachable!(),
-            };
+            let jump = jump.trim_start();
+            j = JUMPS
+                .iter()
+                .find(|(mnemonic, _)| *mnemonic == jump)
+                .map(|(_, bits)| *bits)
+                .ok_or_else(|| {
+                    let error = Error::new(
+                        offset(s, jump),
+                        jump,
+                        format!("unknown jump mnemonic `{jump}`"),
+                    );
+                    match nearest(jump, JUMPS.iter().map(|(mnemonic, _)| *mnemonic)) {
+                        Some(m) => error.with_suggestion(format!("did you mean `{m}`?")),
+                        None => error,
+                    }
+                })?;
 
             comp.trim_end()
         } else {
             remaining
         };
 
-        let c: u16 = match comp {
-            "0" => 0b010_1010,
-            "1" => 0b011_1111,
-            "-1" => 0b011_1010,
-            "D" => 0b000_1100,
-            "A" => 0b011_0000,
-            "M" => 0b111_0000,
-            "!D" => 0b000_1101,
-            "!A" => 0b011_0001,
-            "!M" => 0b111_0001,
-            "-D" => 0b000_1111,
-            "-A" => 0b011_0011,
-            "-M" => 0b111_0011,
-            "D+1" => 0b001_1111,
-            "A+1" => 0b011_0111,
-            "M+1" => 0b111_0111,
-            "D-1" => 0b000_1110,
-            "A-1" => 0b011_0010,
-            "M-1" => 0b111_0010,
-            "D+A" => 0b000_0010,
-            "D+M" => 0b100_0010,
-            "D-A" => 0b001_0011,
-            "D-M" => 0b101_0011,
-            "A-D" => 0b000_0111,
-            "M-D" => 0b100_0111,
-            "D&A" => 0b000_0000,
-            "D&M" => 0b100_0000,
-            "D|A" => 0b001_0101,
-            "D|M" => 0b101_0101,
-            _ => unreachable!(),
+        let mnemonic = match normalize_comp(comp) {
+            Some(m) if strict && m != comp => {
+                return Err(Error::new(
+                    offset(s, comp),
+                    comp,
+                    format!("`{comp}` is not a canonical comp mnemonic"),
+                )
+                .with_suggestion(format!("write `{m}`")));
+            }
+            m => m,
         };
+        let c = COMPS
+            .iter()
+            .find(|(m, _)| Some(*m) == mnemonic)
+            .map(|(_, bits)| *bits)
+            .ok_or_else(|| {
+                if comp.is_empty() {
+                    return Error::new(offset(s, comp), comp, "missing computation".to_string());
+                }
+                let error = Error::new(
+                    offset(s, comp),
+                    comp,
+                    format!("unknown comp mnemonic `{comp}`"),
+                );
+                match nearest(comp, COMPS.iter().map(|(mnemonic, _)| *mnemonic)) {
+                    Some(m) => error.with_suggestion(format!("did you mean `{m}`?")),
+                    None => error,
+                }
+            })?;
 
-        (0b1110_0000_0000_0000) | (c << 6 | (d << 3) | j)
+        Ok(D((0b1110_0000_0000_0000) | (c << 6 | (d << 3) | j)))
+    }
+
+    pub fn resolve(&self) -> u16 {
+        self.0
     }
 }
@@ -1,8 +1,25 @@
+use crate::diagnostic::Error;
+use crate::symbol_tables::is_valid_symbol;
+
 pub struct Label<'a>(&'a str);
 
 impl Label<'_> {
-    pub fn from(s: &str) -> Label {
-        Label(s)
+    pub fn parse(s: &str) -> Result<Label<'_>, Error> {
+        if !s.ends_with(')') {
+            return Err(Error::new(0, s, format!("unterminated label `{s}`"))
+                .with_suggestion("labels are written as `(NAME)`".to_string()));
+        }
+
+        let label = Label(s);
+        let name = label.get_label();
+        if !is_valid_symbol(name) {
+            return Err(Error::new(1, name, format!("invalid label name `{name}`")).with_suggestion(
+                "symbols are made of letters, digits, `_`, `.`, `$` and `:` and do not begin with a digit"
+                    .to_string(),
+            ));
+        }
+
+        Ok(label)
     }
 
     pub fn get_label(&self) -> &str {
@@ -0,0 +1,19 @@
+pub mod diagnostic;
+pub mod disassembler;
+pub mod formatter;
+pub mod hack;
+pub mod instruction;
+pub mod label;
+pub mod object;
+pub mod optimizer;
+pub mod output;
+pub mod parser;
+pub mod preprocessor;
+pub mod symbol_tables;
+
+mod assembler;
+
+pub use assembler::{
+    assemble, assemble_with, parse_source_map, Location, Options, Program, Symbol,
+};
+pub use diagnostic::Diagnostics;
@@ -1,91 +1,99 @@
-mod instruction;
-mod label;
-mod parser;
-mod symbol_tables;
-
 use std::env;
 use std::fs::{self};
-use std::io::{BufWriter, Write};
+use std::path::Path;
 
 use anyhow::{bail, Context, Result};
 
-use parser::{parse, Kind};
-use symbol_tables::SymbolsTable;
+use asm::object::compile;
+use asm::output::Format;
+use asm::{assemble_with, Options};
+
+const USAGE: &str =
+    "[--format hack|raw-le|raw-be|ihex|logisim|c|rust] [-O] [--strict] [-c] [--lst] [--sym] [--map] file";
 
 fn main() -> Result<()> {
     let arguments: Vec<String> = env::args().collect();
-    if arguments.len() != 2 {
-        bail!(
-            "Usage: {} file",
-            arguments.get(0).unwrap_or(&"asm".to_string())
-        );
+    let usage = || {
+        format!(
+            "Usage: {} {USAGE}",
+            arguments.first().unwrap_or(&"asm".to_string())
+        )
+    };
+
+    let mut optimize = false;
+    let mut object = false;
+    let mut strict = false;
+    let mut write_listing = false;
+    let mut write_symbols = false;
+    let mut write_map = false;
+    let mut format = Format::Hack;
+    let mut file = None;
+    let mut args = arguments.iter().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => {
+                let name = args.next().with_context(usage)?;
+                format = name.parse().map_err(anyhow::Error::msg)?;
+            }
+            "-O" => optimize = true,
+            "-c" => object = true,
+            "--strict" => strict = true,
+            "--lst" => write_listing = true,
+            "--sym" => write_symbols = true,
+            "--map" => write_map = true,
+            _ if file.is_none() => file = Some(arg),
+            _ => bail!(usage()),
+        }
     }
+    let Some(file) = file else {
+        bail!(usage());
+    };
 
-    if !std::path::Path::new(&arguments[1])
+    let path = Path::new(file);
+    if !path
         .extension()
         .is_some_and(|ext| ext.eq_ignore_ascii_case("asm"))
     {
         bail!("Input file must be .asm file.");
     }
 
-    let contents = fs::read_to_string(&arguments[1])
-        .with_context(|| format!("Failed to read {}", arguments[1]))?;
-    let instructions: Vec<Kind> = contents.lines().filter_map(parse).collect();
-
-    let mut symbols_table = SymbolsTable::new();
+    let contents = fs::read_to_string(path).with_context(|| format!("Failed to read {file}"))?;
+    let options = Options {
+        include_dir: path.parent().map(|p| p.to_path_buf()),
+        optimize,
+        strict,
+    };
 
-    // First pass to build symbol table
-    let mut line_num: u16 = 0;
-    for kind in &instructions {
-        match kind {
-            parser::Kind::Instruction(_) => {
-                line_num += 1;
-            }
-            parser::Kind::Label(name) => {
-                let label = name.get_label();
-                if symbols_table.contains_key(label) {
-                    bail!(
-                        "Failed to build up the symbol table because of the duplicate symbol {}",
-                        label
-                    );
-                }
-                symbols_table.insert_label(label.to_string(), line_num);
-            }
-        }
+    // `-c` leaves symbols defined elsewhere to the linker
+    if object {
+        let object = compile(&contents, &options).map_err(|d| d.in_file(file))?;
+        let path = path.with_extension("hobj");
+        fs::write(&path, object.to_string())
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        return Ok(());
     }
 
-    // Second pass to generate code, assume little endian
-    let mut binary_code: Vec<u16> = Vec::new();
+    let program = assemble_with(&contents, &options).map_err(|d| d.in_file(file))?;
 
-    for kind in &instructions {
-        match kind {
-            parser::Kind::Instruction(ins) => match ins {
-                instruction::Instruction::A(a) => {
-                    binary_code.push(a.resolve(&mut symbols_table));
-                }
-                instruction::Instruction::D(d) => {
-                    binary_code.push(d.resolve());
-                }
-            },
-            parser::Kind::Label(_) => {}
-        }
-    }
+    let out_filename = path.with_extension(format.extension());
+    let name = path.file_stem().unwrap_or_default().to_string_lossy();
+    fs::write(&out_filename, format.encode(&program.words, &name))
+        .with_context(|| format!("Failed to write {}", out_filename.display()))?;
 
-    let out_filename = {
-        let s = arguments[1].strip_suffix(".asm").unwrap();
-        String::from(s) + ".hack"
-    };
-    let mut writer = BufWriter::new(
-        fs::File::options()
-            .write(true)
-            .truncate(true)
-            .create(true)
-            .open(&out_filename)
-            .with_context(|| format!("Failed to open {}", &out_filename))?,
-    );
-
-    for b in binary_code {
-        writeln!(&mut writer, "{b:016b}").unwrap();
+    if write_listing {
+        let path = path.with_extension("lst");
+        fs::write(&path, program.listing())
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    if write_symbols {
+        let path = path.with_extension("sym");
+        fs::write(&path, program.symbol_file())
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    if write_map {
+        let path = path.with_extension("map");
+        fs::write(&path, program.source_map_file(file))
+            .with_context(|| format!("Failed to write {}", path.display()))?;
     }
 
     Ok(())
@@ -0,0 +1,245 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::assembler::{first_pass, Options};
+use crate::diagnostic::Diagnostics;
+use crate::instruction::Instruction;
+use crate::parser::Kind;
+use crate::preprocessor::preprocess;
+use crate::symbol_tables::{SymbolKind, SymbolsTable, ROM_SIZE, VARIABLE_END};
+
+/// A relocatable object: code assembled as if it started at ROM address 0.
+///
+/// Its text form is
+///
+/// ```text
+/// hobj
+/// export LOOP 4      label defined at offset 4
+/// reloc 9            the word at offset 9 holds an offset to relocate
+/// ref 12 Main.fib    the word at offset 12 takes the address of a symbol defined elsewhere
+/// code
+/// 0000000000000100   one word per line, as in .hack files
+/// ```
+///
+/// Only labels with a `.`, such as `Main.fib`, are exported. Others, such as `LOOP`, are
+/// local to their object, so that several objects can use the same name. A `ref` symbol
+/// that no object exports is a variable, allocated by the linker.
+#[derive(Default)]
+pub struct Object {
+    pub words: Vec<u16>,
+    pub exports: BTreeMap<String, u16>,
+    pub relocations: Vec<u16>,
+    pub references: Vec<(u16, String)>,
+}
+
+/// Assemble `source` into an object, leaving references to symbols it does not define
+/// (and all variables) to the linker
+pub fn compile(source: &str, options: &Options) -> Result<Object, Diagnostics> {
+    let include_dir = options.include_dir.clone().unwrap_or_default();
+    let (lines, mut diagnostics) = preprocess(source, &include_dir);
+    let (instructions, symbols_table) = first_pass(&lines, options, &mut diagnostics);
+
+    if !diagnostics.is_empty() {
+        return Err(diagnostics);
+    }
+
+    let mut object = Object::default();
+    for (_, kind) in &instructions {
+        let offset = object.words.len() as u16;
+        match kind {
+            Kind::Instruction(Instruction::A(a)) => {
+                let value = a.value();
+                let word = if let Ok(num) = value.parse::<u16>() {
+                    num
+                } else {
+                    match symbols_table.kind(value) {
+                        Some(SymbolKind::Predefined) => *symbols_table.get(value).unwrap(),
+                        Some(_) => {
+                            object.relocations.push(offset);
+                            *symbols_table.get(value).unwrap()
+                        }
+                        None => {
+                            object.references.push((offset, value.to_string()));
+                            0
+                        }
+                    }
+                };
+                object.words.push(word);
+            }
+            Kind::Instruction(Instruction::D(d)) => object.words.push(d.resolve()),
+            Kind::Label(_) => {}
+        }
+    }
+    object.exports = symbols_table
+        .user_symbols()
+        .filter(|(name, _, _)| is_exported(name))
+        .map(|(name, address, _)| (name.to_string(), address))
+        .collect();
+
+    Ok(object)
+}
+
+/// Whether a label is visible to other objects
+pub fn is_exported(label: &str) -> bool {
+    label.contains('.')
+}
+
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "hobj")?;
+        let mut exports: Vec<(&String, &u16)> = self.exports.iter().collect();
+        exports.sort_by_key(|(name, offset)| (**offset, *name));
+        for (name, offset) in exports {
+            writeln!(f, "export {name} {offset}")?;
+        }
+        for offset in &self.relocations {
+            writeln!(f, "reloc {offset}")?;
+        }
+        for (offset, name) in &self.references {
+            writeln!(f, "ref {offset} {name}")?;
+        }
+        writeln!(f, "code")?;
+        for word in &self.words {
+            writeln!(f, "{word:016b}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Object {
+    pub fn parse(contents: &str) -> Result<Object, String> {
+        let mut lines = contents.lines().enumerate();
+        if lines.next().map(|(_, l)| l.trim()) != Some("hobj") {
+            return Err("not an object file".to_string());
+        }
+
+        let mut object = Object::default();
+        let malformed = |index: usize| format!("line {}: malformed record", index + 1);
+        for (index, line) in lines.by_ref() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let offset = |s: &str| s.parse::<u16>().map_err(|_| malformed(index));
+            match fields.as_slice() {
+                ["export", name, o] => {
+                    object.exports.insert(name.to_string(), offset(o)?);
+                }
+                ["reloc", o] => object.relocations.push(offset(o)?),
+                ["ref", o, name] => object.references.push((offset(o)?, name.to_string())),
+                ["code"] => break,
+                [] => {}
+                _ => return Err(malformed(index)),
+            }
+        }
+        for (index, line) in lines {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            match u16::from_str_radix(line, 2) {
+                Ok(word) if line.len() == 16 => object.words.push(word),
+                _ => return Err(format!("line {}: not a 16-bit word", index + 1)),
+            }
+        }
+
+        let size = object.words.len();
+        let out_of_range = object
+            .relocations
+            .iter()
+            .chain(object.references.iter().map(|(o, _)| o))
+            .find(|o| **o as usize >= size);
+        if let Some(o) = out_of_range {
+            return Err(format!("offset {o} is outside of the code"));
+        }
+        // A label may also end the code
+        if let Some((name, o)) = object.exports.iter().find(|(_, o)| **o as usize > size) {
+            return Err(format!("`{name}` at offset {o} is outside of the code"));
+        }
+
+        Ok(object)
+    }
+}
+
+/// Everything that went wrong while linking
+pub struct LinkError(pub Vec<String>);
+
+impl fmt::Display for LinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for error in &self.0 {
+            writeln!(f, "error: {error}")?;
+        }
+        match self.0.len() {
+            1 => write!(f, "aborting due to 1 error"),
+            n => write!(f, "aborting due to {n} errors"),
+        }
+    }
+}
+
+impl fmt::Debug for LinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl std::error::Error for LinkError {}
+
+/// Place `objects` one after the other in ROM, in the given order, and resolve their
+/// references. Variables are allocated from a single `SymbolsTable` in order of first
+/// reference. The names are only used in error messages.
+pub fn link(objects: &[(String, Object)]) -> Result<Vec<u16>, LinkError> {
+    let mut errors = Vec::new();
+    let size: usize = objects.iter().map(|(_, o)| o.words.len()).sum();
+    if size > ROM_SIZE {
+        errors.push(format!(
+            "ROM overflow: the objects have {size} instructions but the ROM holds {ROM_SIZE}"
+        ));
+        return Err(LinkError(errors));
+    }
+
+    let mut symbols_table = SymbolsTable::new();
+    let mut defined_in: BTreeMap<&str, &str> = BTreeMap::new();
+    let mut base = 0;
+    for (name, object) in objects {
+        for (label, offset) in &object.exports {
+            if let Some(other) = defined_in.insert(label, name) {
+                errors.push(format!("`{label}` is defined in both {other} and {name}"));
+                continue;
+            }
+            symbols_table.insert_label(label.clone(), base + offset);
+        }
+        base += object.words.len() as u16;
+    }
+
+    let mut words = Vec::with_capacity(size);
+    for (name, object) in objects {
+        let base = words.len() as u16;
+        let start = words.len();
+        words.extend(&object.words);
+
+        for offset in &object.relocations {
+            words[start + *offset as usize] += base;
+        }
+
+        for (offset, symbol) in &object.references {
+            let address = match symbols_table.get(symbol) {
+                Some(address) => *address,
+                None => {
+                    let address = symbols_table.insert_variable(symbol.clone());
+                    if address >= VARIABLE_END {
+                        errors.push(format!(
+                            "variable `{symbol}` referenced in {name} would be allocated at RAM[{address}], \
+                             past the {} words available for variables",
+                            VARIABLE_END - 16
+                        ));
+                    }
+                    address
+                }
+            };
+            words[start + *offset as usize] = address;
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(words)
+    } else {
+        Err(LinkError(errors))
+    }
+}
@@ -0,0 +1,111 @@
+use crate::instruction::Instruction;
+use crate::parser::Kind;
+
+const D_EQ_M: u16 = 0b1111_1100_0001_0000;
+const M_EQ_D: u16 = 0b1110_0011_0000_1000;
+
+/// Peephole optimizations, applied until nothing changes:
+///
+/// - `@X` is removed when A already holds `X`
+/// - `@L` and a jump without destination are removed when `(L)` directly follows
+/// - `M=D` right after `D=M` (and `D=M` right after `M=D`) is removed
+/// - instructions after an unconditional jump are removed up to the next label
+///
+/// Any label is assumed to be reachable from anywhere, so nothing is known about A after
+/// one. Code reached by a jump to a numeric address instead of a label is not supported.
+/// The first pass must run after this one to compute the label addresses.
+pub fn optimize<T>(mut instructions: Vec<(T, Kind)>) -> Vec<(T, Kind)> {
+    loop {
+        let keep = mark(&instructions);
+        if keep.iter().all(|k| *k) {
+            return instructions;
+        }
+        let mut keep = keep.into_iter();
+        instructions.retain(|_| keep.next().unwrap());
+    }
+}
+
+fn word(kind: &Kind) -> Option<u16> {
+    match kind {
+        Kind::Instruction(Instruction::D(d)) => Some(d.resolve()),
+        _ => None,
+    }
+}
+
+// Whether `instructions[i..]` is `@L`, a jump without destination and then `(L)`,
+// possibly among other labels
+fn jumps_to_next<T>(instructions: &[(T, Kind)], i: usize) -> bool {
+    let Kind::Instruction(Instruction::A(a)) = &instructions[i].1 else {
+        return false;
+    };
+    match instructions.get(i + 1).and_then(|(_, k)| word(k)) {
+        Some(jump) if jump & 0b111 != 0 && jump & 0b111_000 == 0 => {}
+        _ => return false,
+    }
+
+    instructions[i + 2..]
+        .iter()
+        .map_while(|(_, kind)| match kind {
+            Kind::Label(label) => Some(label.get_label()),
+            _ => None,
+        })
+        .any(|label| label == a.value())
+}
+
+// Which instructions to keep after one scan
+fn mark<T>(instructions: &[(T, Kind)]) -> Vec<bool> {
+    let mut keep = vec![true; instructions.len()];
+    // What A holds, if known
+    let mut a_holds: Option<&str> = None;
+    // The previous kept instruction, if no label came after it
+    let mut previous: Option<&Kind> = None;
+    let mut unreachable = false;
+
+    let mut i = 0;
+    while i < instructions.len() {
+        let kind = &instructions[i].1;
+        match kind {
+            Kind::Label(_) => {
+                a_holds = None;
+                previous = None;
+                unreachable = false;
+            }
+            _ if unreachable => keep[i] = false,
+            Kind::Instruction(Instruction::A(a)) => {
+                if jumps_to_next(instructions, i) {
+                    keep[i] = false;
+                    keep[i + 1] = false;
+                    i += 2;
+                    continue;
+                }
+                if a_holds == Some(a.value()) {
+                    keep[i] = false;
+                } else {
+                    a_holds = Some(a.value());
+                    previous = Some(kind);
+                }
+            }
+            Kind::Instruction(Instruction::D(d)) => {
+                let word = d.resolve();
+                let round_trip = matches!(
+                    (previous.and_then(self::word), word),
+                    (Some(D_EQ_M), M_EQ_D) | (Some(M_EQ_D), D_EQ_M)
+                );
+                if round_trip {
+                    keep[i] = false;
+                } else {
+                    if word & 0b100_000 != 0 {
+                        a_holds = None;
+                    }
+                    if word & 0b111 == 0b111 {
+                        unreachable = true;
+                    }
+                    previous = Some(kind);
+                }
+            }
+        }
+        i += 1;
+    }
+
+    keep
+}
@@ -0,0 +1,173 @@
+use std::fmt::Write;
+use std::str::FromStr;
+
+/// Ways to write out the instruction words of a program
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Format {
+    /// The nand2tetris text format, one `{b:016b}` word per line
+    Hack,
+    /// Raw image, 2 bytes per word, little-endian
+    RawLe,
+    /// Raw image, 2 bytes per word, big-endian
+    RawBe,
+    /// Intel HEX with byte addresses and big-endian words
+    IntelHex,
+    /// Logisim `v2.0 raw` ROM image
+    Logisim,
+    /// C array literal
+    C,
+    /// Rust array literal
+    Rust,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "hack" => Format::Hack,
+            "raw-le" => Format::RawLe,
+            "raw-be" => Format::RawBe,
+            "ihex" => Format::IntelHex,
+            "logisim" => Format::Logisim,
+            "c" => Format::C,
+            "rust" => Format::Rust,
+            _ => {
+                return Err(format!(
+                    "Unknown format {s}. Expect one of hack, raw-le, raw-be, ihex, logisim, c, rust"
+                ))
+            }
+        })
+    }
+}
+
+impl Format {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Hack => "hack",
+            Format::RawLe | Format::RawBe => "bin",
+            Format::IntelHex => "hex",
+            Format::Logisim => "rom",
+            Format::C => "c",
+            Format::Rust => "rs",
+        }
+    }
+
+    /// Encode `words`. `name` is used to name the array of the C and Rust formats.
+    pub fn encode(&self, words: &[u16], name: &str) -> Vec<u8> {
+        match self {
+            Format::Hack => words
+                .iter()
+                .map(|w| format!("{w:016b}\n"))
+                .collect::<String>()
+                .into_bytes(),
+            Format::RawLe => words.iter().flat_map(|w| w.to_le_bytes()).collect(),
+            Format::RawBe => words.iter().flat_map(|w| w.to_be_bytes()).collect(),
+            Format::IntelHex => intel_hex(words).into_bytes(),
+            Format::Logisim => logisim(words).into_bytes(),
+            Format::C => {
+                // ISO C has no zero-length arrays, so an empty program is a single 0
+                let words = if words.is_empty() { &[0][..] } else { words };
+                let mut out = String::from("#include <stdint.h>\n\n");
+                writeln!(
+                    out,
+                    "const uint16_t {}[{}] = {{",
+                    identifier(name),
+                    words.len()
+                )
+                .unwrap();
+                out.push_str(&array_body(words));
+                out.push_str("};\n");
+                out.into_bytes()
+            }
+            Format::Rust => {
+                let mut out = String::new();
+                writeln!(
+                    out,
+                    "pub static {}: [u16; {}] = [",
+                    identifier(name),
+                    words.len()
+                )
+                .unwrap();
+                out.push_str(&array_body(words));
+                out.push_str("];\n");
+                out.into_bytes()
+            }
+        }
+    }
+}
+
+// 16 bytes per data record. 32K words are 64K bytes, so no extended address record is needed.
+fn intel_hex(words: &[u16]) -> String {
+    let bytes: Vec<u8> = words.iter().flat_map(|w| w.to_be_bytes()).collect();
+    let mut out = String::new();
+
+    let mut record = |address: u16, kind: u8, data: &[u8]| {
+        let mut sum = data.len() as u8;
+        sum = sum
+            .wrapping_add((address >> 8) as u8)
+            .wrapping_add(address as u8);
+        sum = sum.wrapping_add(kind);
+        write!(out, ":{:02X}{address:04X}{kind:02X}", data.len()).unwrap();
+        for byte in data {
+            write!(out, "{byte:02X}").unwrap();
+            sum = sum.wrapping_add(*byte);
+        }
+        writeln!(out, "{:02X}", sum.wrapping_neg()).unwrap();
+    };
+
+    for (i, chunk) in bytes.chunks(16).enumerate() {
+        record((i * 16) as u16, 0x00, chunk);
+    }
+    record(0, 0x01, &[]);
+    out
+}
+
+// Runs of 4 or more equal words are written as `count*value`
+fn logisim(words: &[u16]) -> String {
+    let mut out = String::from("v2.0 raw\n");
+    let mut items = Vec::new();
+    let mut i = 0;
+    while i < words.len() {
+        let run = words[i..].iter().take_while(|w| **w == words[i]).count();
+        if run >= 4 {
+            items.push(format!("{run}*{:x}", words[i]));
+            i += run;
+        } else {
+            items.push(format!("{:x}", words[i]));
+            i += 1;
+        }
+    }
+    for line in items.chunks(8) {
+        out.push_str(&line.join(" "));
+        out.push('\n');
+    }
+    out
+}
+
+fn array_body(words: &[u16]) -> String {
+    let mut out = String::new();
+    for line in words.chunks(8) {
+        out.push_str("   ");
+        for word in line {
+            write!(out, " 0x{word:04X},").unwrap();
+        }
+        out.push('\n');
+    }
+    out
+}
+
+// `Pong` becomes `PONG_ROM`
+fn identifier(name: &str) -> String {
+    let mut id: String = name
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() {
+            true => c.to_ascii_uppercase(),
+            false => '_',
+        })
+        .collect();
+    if id.starts_with(|c: char| c.is_ascii_digit()) {
+        id.insert(0, '_');
+    }
+    id + "_ROM"
+}
@@ -1,4 +1,5 @@
 use crate::{
+    diagnostic::Error,
     instruction::{Instruction, A, D},
     label::Label,
 };
@@ -8,23 +9,25 @@ pub enum Kind<'a> {
     Label(Label<'a>),
 }
 
-// Assume each line is valid
-pub fn parse(line: &str) -> Option<Kind> {
+/// Parse one line of assembly. `strict` only accepts the C-instruction spellings of the book.
+pub fn parse(line: &str, strict: bool) -> Result<Option<Kind<'_>>, Error> {
     // Process inline comment
     let comment_start = line.find("//").unwrap_or(line.len());
-    let line = line[..comment_start].trim();
+    let code = &line[..comment_start];
+    let column = code.len() - code.trim_start().len();
+    let line = code.trim();
 
     if line.is_empty() {
-        return None;
+        return Ok(None);
     }
 
-    if line.starts_with('@') {
-        return Some(Kind::Instruction(Instruction::A(A::from(line))));
-    }
-
-    if line.starts_with('(') && line.ends_with(')') {
-        return Some(Kind::Label(Label::from(line)));
-    }
+    let kind = if line.starts_with('@') {
+        A::parse(line).map(|a| Kind::Instruction(Instruction::A(a)))
+    } else if line.starts_with('(') {
+        Label::parse(line).map(Kind::Label)
+    } else {
+        D::parse(line, strict).map(|d| Kind::Instruction(Instruction::D(d)))
+    };
 
-    Some(Kind::Instruction(Instruction::D(D::from(line))))
+    kind.map(Some).map_err(|e| e.shift(column))
 }
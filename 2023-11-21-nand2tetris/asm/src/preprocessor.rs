@@ -0,0 +1,383 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::diagnostic::{Diagnostic, Diagnostics, Error};
+use crate::symbol_tables::is_valid_symbol;
+
+/// A line of plain Hack assembly produced by the preprocessor
+pub struct Line {
+    /// File the line comes from, empty for the main source
+    pub file: String,
+    /// 0-based line in `file`. Lines produced by a macro point at its invocation.
+    pub index: usize,
+    pub text: String,
+}
+
+struct Macro {
+    params: Vec<String>,
+    body: Vec<String>,
+}
+
+// An open `.if`
+struct Branch {
+    taken: bool,
+    seen_else: bool,
+    index: usize,
+    line: String,
+}
+
+struct Preprocessor<'a> {
+    include_dir: &'a Path,
+    constants: HashMap<String, String>,
+    macros: HashMap<String, Macro>,
+    expansions: usize,
+    // Canonical paths of the files being included and names of the macros being
+    // expanded, innermost last, to catch cycles
+    including: Vec<PathBuf>,
+    expanding: Vec<String>,
+    diagnostics: Diagnostics,
+    lines: Vec<Line>,
+}
+
+/// Expand the directives in `source`:
+///
+/// - `.equ NAME value` defines a constant usable as `@NAME`, in `.if` and in macro arguments
+/// - `.include "file.asm"` inserts a file, relative to `include_dir` in `source` and to
+///   the directory of the including file in included files
+/// - `.macro NAME a, b` ... `.endm` defines a macro, invoked as `NAME x, y`. The body refers
+///   to its parameters as `\a` and `\b`, and labels defined in the body are renamed for each
+///   expansion so that a macro can be used more than once.
+/// - `.if value` ... [`.else` ...] `.endif` keeps the lines of the taken branch. A value is
+///   true when it is not 0.
+///
+/// Lines that could be expanded are returned alongside the errors, so that the caller can
+/// keep checking them.
+pub fn preprocess(source: &str, include_dir: &Path) -> (Vec<Line>, Diagnostics) {
+    let mut preprocessor = Preprocessor {
+        include_dir,
+        constants: HashMap::new(),
+        macros: HashMap::new(),
+        expansions: 0,
+        including: Vec::new(),
+        expanding: Vec::new(),
+        diagnostics: Diagnostics::new(),
+        lines: Vec::new(),
+    };
+
+    let lines = source
+        .lines()
+        .enumerate()
+        .map(|(i, l)| (i, l.to_string()))
+        .collect();
+    preprocessor.process("", lines);
+
+    (preprocessor.lines, preprocessor.diagnostics)
+}
+
+// Code of a line without comment and surrounding whitespace, and its column
+fn split_code(line: &str) -> (usize, &str) {
+    let comment_start = line.find("//").unwrap_or(line.len());
+    let code = &line[..comment_start];
+    (code.len() - code.trim_start().len(), code.trim())
+}
+
+// Made of `A`, `D` and `M`, like a dest or the first operand of a comp
+fn is_registers(name: &str) -> bool {
+    name.chars().all(|c| matches!(c, 'A' | 'D' | 'M'))
+}
+
+impl Preprocessor<'_> {
+    fn error(&mut self, file: &str, index: usize, source: &str, error: Error) {
+        let mut diagnostic = Diagnostic::new(index, source, error);
+        diagnostic.file = file.to_string();
+        self.diagnostics.push(diagnostic);
+    }
+
+    fn process(&mut self, file: &str, lines: Vec<(usize, String)>) {
+        let mut branches: Vec<Branch> = Vec::new();
+        let active = |branches: &[Branch]| branches.iter().all(|b| b.taken);
+
+        let mut lines = lines.into_iter();
+        while let Some((index, line)) = lines.next() {
+            let (column, code) = split_code(&line);
+            let (word, rest) = code
+                .split_once(char::is_whitespace)
+                .map_or((code, ""), |(w, r)| (w, r.trim()));
+            let at = |text: &str, message: String| {
+                Error::new(column + code.find(text).unwrap_or(0), text, message)
+            };
+
+            match word {
+                ".if" => {
+                    let taken = active(&branches)
+                        && self.evaluate(rest).map(|v| v != 0).unwrap_or_else(|| {
+                            let error = at(rest, format!("`{rest}` is not a number or a constant"));
+                            self.error(file, index, &line, error);
+                            false
+                        });
+                    branches.push(Branch {
+                        taken,
+                        seen_else: false,
+                        index,
+                        line: line.clone(),
+                    });
+                    continue;
+                }
+                ".else" | ".endif" => {
+                    let parent = active(&branches[..branches.len().saturating_sub(1)]);
+                    match (word, branches.last_mut()) {
+                        (".else", Some(branch)) if !branch.seen_else => {
+                            branch.taken = parent && !branch.taken;
+                            branch.seen_else = true;
+                        }
+                        (".endif", Some(_)) => {
+                            branches.pop();
+                        }
+                        _ => {
+                            let error = at(word, format!("`{word}` without a matching `.if`"));
+                            self.error(file, index, &line, error);
+                        }
+                    }
+                    continue;
+                }
+                _ if !active(&branches) => {
+                    // Macro bodies in a skipped branch are skipped as well
+                    if word == ".macro" {
+                        for (_, line) in lines.by_ref() {
+                            if split_code(&line).1 == ".endm" {
+                                break;
+                            }
+                        }
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+
+            match word {
+                ".equ" => {
+                    let Some((name, value)) = rest.split_once(char::is_whitespace) else {
+                        let error = at(code, "expect `.equ NAME value`".to_string());
+                        self.error(file, index, &line, error);
+                        continue;
+                    };
+                    let value = value.trim();
+                    if !is_valid_symbol(name) {
+                        let error = at(name, format!("invalid constant name `{name}`"));
+                        self.error(file, index, &line, error);
+                    } else if self.constants.contains_key(name) {
+                        let error = at(name, format!("constant `{name}` is already defined"));
+                        self.error(file, index, &line, error);
+                    } else if let Some(value) = self.evaluate(value) {
+                        self.constants.insert(name.to_string(), value.to_string());
+                    } else {
+                        let error = at(value, format!("`{value}` is not a number or a constant"));
+                        self.error(file, index, &line, error);
+                    }
+                }
+                ".include" => {
+                    let Some(name) = rest.strip_prefix('"').and_then(|r| r.strip_suffix('"'))
+                    else {
+                        let error = at(rest, "expect `.include \"file.asm\"`".to_string());
+                        self.error(file, index, &line, error);
+                        continue;
+                    };
+                    let dir = match file {
+                        "" => self.include_dir,
+                        file => Path::new(file).parent().unwrap_or(self.include_dir),
+                    };
+                    let path: PathBuf = dir.join(name);
+                    let canonical = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+                    if let Some(start) = self.including.iter().position(|p| *p == canonical) {
+                        let cycle: Vec<String> = self.including[start..]
+                            .iter()
+                            .chain([&canonical])
+                            .map(|p| p.display().to_string())
+                            .collect();
+                        let error = at(
+                            rest,
+                            format!("`{name}` is included recursively: {}", cycle.join(" -> ")),
+                        );
+                        self.error(file, index, &line, error);
+                        continue;
+                    }
+                    match fs::read_to_string(&path) {
+                        Ok(contents) => {
+                            let lines = contents
+                                .lines()
+                                .enumerate()
+                                .map(|(i, l)| (i, l.to_string()))
+                                .collect();
+                            self.including.push(canonical);
+                            self.process(&path.display().to_string(), lines);
+                            self.including.pop();
+                        }
+                        Err(e) => {
+                            let error =
+                                at(rest, format!("failed to read `{}`: {e}", path.display()));
+                            self.error(file, index, &line, error);
+                        }
+                    }
+                }
+                ".macro" => {
+                    let (name, params) = rest
+                        .split_once(char::is_whitespace)
+                        .map_or((rest, ""), |(n, p)| (n, p.trim()));
+                    let params: Vec<String> = params
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|p| !p.is_empty())
+                        .map(str::to_string)
+                        .collect();
+
+                    let mut body = Vec::new();
+                    let mut terminated = false;
+                    for (_, line) in lines.by_ref() {
+                        if split_code(&line).1 == ".endm" {
+                            terminated = true;
+                            break;
+                        }
+                        body.push(line);
+                    }
+
+                    if !terminated {
+                        let error = at(word, "`.macro` without a matching `.endm`".to_string());
+                        self.error(file, index, &line, error);
+                    } else if !is_valid_symbol(name) || name.starts_with('.') {
+                        let error = at(name, format!("invalid macro name `{name}`"));
+                        self.error(file, index, &line, error);
+                    } else if is_registers(name) {
+                        // It would take over C-instructions such as `M = D`
+                        let error = at(name, format!("macro name `{name}` is a register name"));
+                        self.error(file, index, &line, error);
+                    } else if self.macros.contains_key(name) {
+                        let error = at(name, format!("macro `{name}` is already defined"));
+                        self.error(file, index, &line, error);
+                    } else if let Some(p) = params.iter().find(|p| !is_valid_symbol(p)) {
+                        let error = at(p, format!("invalid parameter name `{p}`"));
+                        self.error(file, index, &line, error);
+                    } else {
+                        self.macros.insert(name.to_string(), Macro { params, body });
+                    }
+                }
+                ".endm" => {
+                    let error = at(word, "`.endm` without a matching `.macro`".to_string());
+                    self.error(file, index, &line, error);
+                }
+                _ if word.starts_with('.') => {
+                    let error = at(word, format!("unknown directive `{word}`"));
+                    self.error(file, index, &line, error);
+                }
+                _ if self.macros.contains_key(word) => {
+                    if let Some(start) = self.expanding.iter().position(|m| m == word) {
+                        let cycle = [&self.expanding[start..], &[word.to_string()]].concat();
+                        let error = at(
+                            word,
+                            format!("macro `{word}` expands recursively: {}", cycle.join(" -> ")),
+                        );
+                        self.error(file, index, &line, error);
+                        continue;
+                    }
+                    let args: Vec<&str> = rest
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|a| !a.is_empty())
+                        .collect();
+                    match self.expand(word, &args) {
+                        Ok(body) => {
+                            let lines = body.into_iter().map(|l| (index, l)).collect();
+                            self.expanding.push(word.to_string());
+                            self.process(file, lines);
+                            self.expanding.pop();
+                        }
+                        Err(message) => {
+                            let error = at(code, message);
+                            self.error(file, index, &line, error);
+                        }
+                    }
+                }
+                _ => {
+                    // `@NAME` of a constant becomes `@value`
+                    let text = match code
+                        .strip_prefix('@')
+                        .and_then(|name| self.constants.get(name))
+                    {
+                        Some(value) => format!("@{value}"),
+                        None => line,
+                    };
+                    self.lines.push(Line {
+                        file: file.to_string(),
+                        index,
+                        text,
+                    });
+                }
+            }
+        }
+
+        for branch in branches {
+            let error = Error::new(
+                branch.line.find(".if").unwrap_or(0),
+                ".if",
+                "`.if` without a matching `.endif`".to_string(),
+            );
+            self.error(file, branch.index, &branch.line, error);
+        }
+    }
+
+    // A number or the name of a constant
+    fn evaluate(&self, value: &str) -> Option<i32> {
+        let value = self.constants.get(value).map_or(value, String::as_str);
+        value.parse::<i32>().ok()
+    }
+
+    // Substitute the arguments and rename the labels of the macro body
+    fn expand(&mut self, name: &str, args: &[&str]) -> Result<Vec<String>, String> {
+        let m = &self.macros[name];
+        if args.len() != m.params.len() {
+            return Err(format!(
+                "macro `{name}` takes {} argument(s) but {} were given",
+                m.params.len(),
+                args.len()
+            ));
+        }
+
+        self.expansions += 1;
+        let labels: Vec<&str> = m
+            .body
+            .iter()
+            .filter_map(|line| {
+                let (_, code) = split_code(line);
+                code.strip_prefix('(')?.strip_suffix(')')
+            })
+            .collect();
+
+        // Longer parameters first so that `\ab` is not replaced by `\a`
+        let mut params: Vec<(&String, &str)> = m.params.iter().zip(args.iter().copied()).collect();
+        params.sort_by_key(|(p, _)| std::cmp::Reverse(p.len()));
+
+        let body = m
+            .body
+            .iter()
+            .map(|line| {
+                let mut line = line.clone();
+                for (param, arg) in &params {
+                    line = line.replace(&format!("\\{param}"), arg);
+                }
+                let (_, code) = split_code(&line);
+                let local = code
+                    .strip_prefix('@')
+                    .or_else(|| code.strip_prefix('(').and_then(|c| c.strip_suffix(')')))
+                    .filter(|symbol| labels.contains(symbol));
+                match local {
+                    Some(label) => {
+                        line.replacen(label, &format!("{name}:{}:{label}", self.expansions), 1)
+                    }
+                    None => line,
+                }
+            })
+            .collect();
+
+        Ok(body)
+    }
+}
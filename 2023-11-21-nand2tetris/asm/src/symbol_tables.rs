@@ -1,38 +1,67 @@
 use std::collections::HashMap;
 
+// Symbols: A symbol can be any sequence of letters, digits,
+// underscore (_), dot (.), dollar sign ($), and colon (:) that does not begin with a digit.
+pub fn is_valid_symbol(s: &str) -> bool {
+    !s.is_empty()
+        && !s.starts_with(|c: char| c.is_ascii_digit())
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '$' | ':'))
+}
+
+const PREDEFINED: [(&str, u16); 23] = [
+    ("R0", 0),
+    ("R1", 1),
+    ("R2", 2),
+    ("R3", 3),
+    ("R4", 4),
+    ("R5", 5),
+    ("R6", 6),
+    ("R7", 7),
+    ("R8", 8),
+    ("R9", 9),
+    ("R10", 10),
+    ("R11", 11),
+    ("R12", 12),
+    ("R13", 13),
+    ("R14", 14),
+    ("R15", 15),
+    ("SP", 0),
+    ("LCL", 1),
+    ("ARG", 2),
+    ("THIS", 3),
+    ("THAT", 4),
+    ("SCREEN", 16384),
+    ("KBD", 24576),
+];
+
+pub const ROM_SIZE: usize = 32768;
+/// Variables live in RAM[16..16383]; the screen memory map starts right after
+pub const VARIABLE_END: u16 = 16384;
+
+pub fn is_predefined(s: &str) -> bool {
+    PREDEFINED.iter().any(|(k, _)| *k == s)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SymbolKind {
+    Predefined,
+    Label,
+    Variable,
+}
+
 pub struct SymbolsTable {
-    symbols_table: HashMap<String, u16>,
+    symbols_table: HashMap<String, (u16, SymbolKind)>,
     next_value: u16,
 }
 
 impl SymbolsTable {
     pub fn new() -> SymbolsTable {
         SymbolsTable {
-            symbols_table: HashMap::from([
-                ("R0".to_string(), 0),
-                ("R1".to_string(), 1),
-                ("R2".to_string(), 2),
-                ("R3".to_string(), 3),
-                ("R4".to_string(), 4),
-                ("R5".to_string(), 5),
-                ("R6".to_string(), 6),
-                ("R7".to_string(), 7),
-                ("R8".to_string(), 8),
-                ("R9".to_string(), 9),
-                ("R10".to_string(), 10),
-                ("R11".to_string(), 11),
-                ("R12".to_string(), 12),
-                ("R13".to_string(), 13),
-                ("R14".to_string(), 14),
-                ("R15".to_string(), 15),
-                ("SP".to_string(), 0),
-                ("LCL".to_string(), 1),
-                ("ARG".to_string(), 2),
-                ("THIS".to_string(), 3),
-                ("THAT".to_string(), 4),
-                ("SCREEN".to_string(), 16384),
-                ("KBD".to_string(), 24576),
-            ]),
+            symbols_table: PREDEFINED
+                .iter()
+                .map(|(k, v)| (k.to_string(), (*v, SymbolKind::Predefined)))
+                .collect(),
             next_value: 16,
         }
     }
@@ -42,15 +71,39 @@ impl SymbolsTable {
     }
 
     pub fn get(&self, k: &str) -> Option<&u16> {
-        self.symbols_table.get(k)
+        self.symbols_table.get(k).map(|(v, _)| v)
     }
 
-    pub fn insert_variable(&mut self, k: String) {
-        self.symbols_table.insert(k, self.next_value);
-        self.next_value += 1;
+    pub fn kind(&self, k: &str) -> Option<SymbolKind> {
+        self.symbols_table.get(k).map(|(_, kind)| *kind)
+    }
+
+    /// Labels and variables added after construction
+    pub fn user_symbols(&self) -> impl Iterator<Item = (&str, u16, SymbolKind)> {
+        self.symbols_table
+            .iter()
+            .filter(|(_, (_, kind))| *kind != SymbolKind::Predefined)
+            .map(|(k, (v, kind))| (k.as_str(), *v, *kind))
+    }
+
+    /// Allocate the next free RAM word to `k` and return its address. Addresses past
+    /// `VARIABLE_END` run into memory-mapped I/O and should be reported by the caller.
+    pub fn insert_variable(&mut self, k: String) -> u16 {
+        let address = self.next_value;
+        self.symbols_table
+            .insert(k, (address, SymbolKind::Variable));
+        self.next_value = self.next_value.saturating_add(1);
+        address
     }
 
     pub fn insert_label(&mut self, k: String, line_number: u16) {
-        self.symbols_table.insert(k, line_number);
+        self.symbols_table
+            .insert(k, (line_number, SymbolKind::Label));
+    }
+}
+
+impl Default for SymbolsTable {
+    fn default() -> Self {
+        Self::new()
     }
 }
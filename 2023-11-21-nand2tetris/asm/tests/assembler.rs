@@ -0,0 +1,134 @@
+use asm::symbol_tables::SymbolKind;
+use asm::{assemble, assemble_with, Options};
+
+#[test]
+fn label_after_a_full_rom_is_an_overflow() {
+    let full = "D=0\n".repeat(32768);
+    assert_eq!(assemble(&full).unwrap().words.len(), 32768);
+
+    let source = format!("@END\n{}(END)\n", "D=0\n".repeat(32767));
+    let error = assemble(&source).err().unwrap().to_string();
+    assert!(
+        error.contains("ROM overflow: label `END` would be at address 32768"),
+        "{error}"
+    );
+
+    // The last address is fine
+    let source = format!("{}(END)\n@END\n", "D=0\n".repeat(32767));
+    assert_eq!(assemble(&source).unwrap().words[32767], 32767);
+}
+
+fn strict(source: &str) -> Result<Vec<u16>, String> {
+    let options = Options {
+        strict: true,
+        ..Default::default()
+    };
+    assemble_with(source, &options)
+        .map(|program| program.words)
+        .map_err(|d| d.to_string())
+}
+
+#[test]
+fn strict_accepts_the_dest_spellings_of_both_editions() {
+    assert_eq!(strict("DM=M+1"), strict("MD=M+1"));
+    assert_eq!(strict("ADM=M+1"), strict("AMD=M+1"));
+    assert_eq!(strict("DM=M+1\nADM=M+1"), Ok(vec![0xFDD8, 0xFDF8]));
+
+    for dest in ["MA", "DA", "MAD", "MDA", "DAM", "DMA"] {
+        let error = strict(&format!("{dest}=M+1")).unwrap_err();
+        assert!(error.contains("is not in canonical order"), "{error}");
+    }
+}
+
+fn vm_commands(source: &str) -> Vec<(Option<String>, Option<String>)> {
+    assemble(source)
+        .unwrap()
+        .source_map
+        .into_iter()
+        .map(|location| (location.function, location.vm_command))
+        .collect()
+}
+
+fn some(function: &str, command: &str) -> (Option<String>, Option<String>) {
+    (Some(function.to_string()), Some(command.to_string()))
+}
+
+#[test]
+fn plain_comments_are_not_vm_commands() {
+    let source = "// add the two values\n@1\n// push x\nD=A\n    // push constant 1\nD=D+A\n";
+    assert_eq!(vm_commands(source), vec![(None, None); 3]);
+}
+
+#[test]
+fn shared_routines_belong_to_no_vm_command() {
+    // Shaped like the output of `hvm --shared`
+    let source = "\
+@Sys.init
+0;JMP
+($$call)
+@R15
+M=D
+// function Sys.init 0
+(Sys.init)
+// call Sys.f 0
+@Sys.init$$ret1
+D=A
+(Sys.init$$ret1)
+// label END
+(Sys.init$END)
+// goto END
+@Sys.init$END
+0;JMP
+($$return)
+@5
+D=-A
+";
+    assert_eq!(
+        vm_commands(source),
+        vec![
+            (None, None),
+            (None, None),
+            (None, None),
+            (None, None),
+            some("Sys.init", "call Sys.f 0"),
+            some("Sys.init", "call Sys.f 0"),
+            some("Sys.init", "goto END"),
+            some("Sys.init", "goto END"),
+            (None, None),
+            (None, None),
+        ]
+    );
+}
+
+#[test]
+fn assemble_returns_words_symbols_and_source_map() {
+    let source = "\
+// Sum 1..n
+@i
+M=1
+(LOOP)
+@i
+D=M
+@LOOP
+0;JMP
+";
+    let program = assemble(source).unwrap();
+    assert_eq!(program.words, [16, 0xEFC8, 16, 0xFC10, 2, 0xEA87]);
+
+    let symbols: Vec<(&str, u16, SymbolKind)> = program
+        .symbols
+        .iter()
+        .map(|(name, symbol)| (name.as_str(), symbol.address, symbol.kind))
+        .collect();
+    assert_eq!(
+        symbols,
+        [
+            ("LOOP", 2, SymbolKind::Label),
+            ("i", 16, SymbolKind::Variable)
+        ]
+    );
+
+    let lines: Vec<usize> = program.source_map.iter().map(|l| l.line).collect();
+    assert_eq!(lines, [2, 3, 5, 6, 7, 8]);
+    assert_eq!(program.source_lines[3], "D=M");
+}
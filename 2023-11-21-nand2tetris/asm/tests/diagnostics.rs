@@ -0,0 +1,64 @@
+use asm::assemble;
+use asm::diagnostic::nearest;
+use asm::instruction::COMPS;
+
+// (line, column, text, message, suggestion) of every error of `source`
+fn errors(source: &str) -> Vec<(usize, usize, String, String, Option<String>)> {
+    assemble(source)
+        .err()
+        .unwrap()
+        .iter()
+        .map(|d| {
+            let e = &d.error;
+            (
+                d.line,
+                e.column,
+                e.text.clone(),
+                e.message.clone(),
+                e.suggestion.clone(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn every_error_of_a_file_is_reported() {
+    let errors = errors("@40000\n  D=X+1\nD;JMPP\n@1\nAD=D+1;JGT\n");
+    assert_eq!(errors.len(), 3);
+
+    let (line, column, text, message, suggestion) = &errors[0];
+    assert_eq!((*line, *column, text.as_str()), (1, 1, "40000"));
+    assert_eq!(message, "constant `40000` does not fit in 15 bits");
+    assert!(suggestion.as_ref().unwrap().contains("@25535"));
+
+    let (line, column, text, message, suggestion) = &errors[1];
+    assert_eq!((*line, *column, text.as_str()), (2, 4, "X+1"));
+    assert_eq!(message, "unknown comp mnemonic `X+1`");
+    assert_eq!(suggestion.as_deref(), Some("did you mean `D+1`?"));
+
+    let (line, column, text, _, suggestion) = &errors[2];
+    assert_eq!((*line, *column, text.as_str()), (3, 2, "JMPP"));
+    assert_eq!(suggestion.as_deref(), Some("did you mean `JMP`?"));
+}
+
+#[test]
+fn diagnostics_point_at_the_text() {
+    let message = assemble("D=X+1\n")
+        .err()
+        .unwrap()
+        .in_file("a.asm")
+        .to_string();
+    assert_eq!(
+        message,
+        "a.asm:1:3: error: unknown comp mnemonic `X+1`\n    D=X+1\n      ^^^\n    \
+         help: did you mean `D+1`?\n\naborting due to 1 error"
+    );
+}
+
+#[test]
+fn nearest_prefers_the_same_characters() {
+    let comps = || COMPS.iter().map(|(m, _)| *m);
+    assert_eq!(nearest("A+D", comps()), Some("D+A"));
+    assert_eq!(nearest("M-11", comps()), Some("M-1"));
+    assert_eq!(nearest("XYZW", comps()), None);
+}
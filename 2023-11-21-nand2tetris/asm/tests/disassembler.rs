@@ -0,0 +1,48 @@
+use asm::assemble;
+use asm::disassembler::{decode, disassemble, is_jump, uses_memory, SymbolFile};
+
+// Every word `decode` accepts assembles back to itself
+#[test]
+fn decode_is_the_inverse_of_the_assembler() {
+    let (a, c): (Vec<u16>, Vec<u16>) = (0..=u16::MAX)
+        .filter(|&word| decode(word).is_some())
+        .partition(|&word| word & 0x8000 == 0);
+    for words in [a, c] {
+        let source: Vec<String> = words.iter().map(|&word| decode(word).unwrap()).collect();
+        let program = assemble(&source.join("\n")).unwrap();
+        assert_eq!(program.words, words);
+    }
+}
+
+#[test]
+fn words_without_bits_13_and_14_are_invalid() {
+    for word in [0x8C10, 0xAC10, 0xCC10, 0x8007, 0x9008] {
+        assert_eq!(decode(word), None, "{word:#06x}");
+        assert!(!is_jump(word), "{word:#06x}");
+        assert!(!uses_memory(word), "{word:#06x}");
+    }
+    assert_eq!(decode(0xEC10).as_deref(), Some("D=A"));
+
+    let error = disassemble(&[0x0005, 0x8C10], &SymbolFile::default()).unwrap_err();
+    assert_eq!((error.address, error.word), (1, 0x8C10));
+}
+
+#[test]
+fn generated_labels_do_not_reuse_names_of_the_symbol_file() {
+    // `L2` names address 0 in the symbol file, so the target 2 needs another name
+    let symbols = SymbolFile::parse("label L2 0\nvariable L2_1 16\n").unwrap();
+    let words = assemble("(A)\n@2\n0;JMP\n@A\n0;JMP\n").unwrap().words;
+    let source = disassemble(&words, &symbols).unwrap();
+    assert_eq!(source, "(L2)\n@L2_2\n0;JMP\n(L2_2)\n@L2\n0;JMP\n");
+    assert_eq!(assemble(&source).unwrap().words, words);
+}
+
+#[test]
+fn every_name_of_an_address_is_kept() {
+    let symbols = SymbolFile::parse("label START 0\nlabel LOOP 0\nvariable i 16\n").unwrap();
+    assert_eq!(symbols.labels[&0], ["START", "LOOP"]);
+
+    let words = assemble("@0\n0;JMP\n").unwrap().words;
+    let source = disassemble(&words, &symbols).unwrap();
+    assert_eq!(source, "(START)\n(LOOP)\n@START\n0;JMP\n");
+}
@@ -0,0 +1,19 @@
+use asm::formatter::{format, Options};
+
+#[test]
+fn address_comments_are_kept_without_addresses() {
+    let source = "@1 // [7] seven\nD=A // [x] not an address\n";
+    let formatted = format(source, &Options::default()).unwrap();
+    assert!(formatted.contains("// [7] seven"), "{formatted}");
+    assert!(formatted.contains("// [x] not an address"), "{formatted}");
+}
+
+#[test]
+fn address_comments_are_regenerated_with_addresses() {
+    let source = "@1 // [7] seven\nD=A\n";
+    let options = Options { addresses: true };
+    let formatted = format(source, &options).unwrap();
+    assert!(formatted.contains("// [0] seven"), "{formatted}");
+    assert!(formatted.contains("// [1]"), "{formatted}");
+    assert!(!formatted.contains("[7]"), "{formatted}");
+}
@@ -0,0 +1,65 @@
+use asm::object::{compile, link, Object};
+use asm::Options;
+
+fn object(source: &str) -> Object {
+    compile(source, &Options::default()).unwrap()
+}
+
+#[test]
+fn local_labels_do_not_clash_between_objects() {
+    let main = object("(LOOP)\n@Lib.f\n0;JMP\n@LOOP\n0;JMP\n(END)\n");
+    let lib = object("(Lib.f)\n@LOOP\n(LOOP)\n0;JMP\n(END)\n@END\n0;JMP\n");
+    assert_eq!(
+        main.exports.keys().collect::<Vec<_>>(),
+        Vec::<&String>::new()
+    );
+    assert_eq!(lib.exports.keys().collect::<Vec<_>>(), ["Lib.f"]);
+
+    let words = link(&[("main".to_string(), main), ("lib".to_string(), lib)]).unwrap();
+    // main at 0..4, lib at 4..8: each `@LOOP` and `@END` stays in its object
+    assert_eq!(words[..4], [4, 0xEA87, 0, 0xEA87]);
+    assert_eq!(words[4..], [5, 0xEA87, 6, 0xEA87]);
+}
+
+#[test]
+fn exported_labels_still_clash() {
+    let a = object("(Lib.f)\n0;JMP\n");
+    let b = object("(Lib.f)\n0;JMP\n");
+    let error = link(&[("a".to_string(), a), ("b".to_string(), b)])
+        .err()
+        .unwrap()
+        .to_string();
+    assert!(
+        error.contains("`Lib.f` is defined in both a and b"),
+        "{error}"
+    );
+}
+
+#[test]
+fn objects_round_trip_through_their_text_form() {
+    let original = object("(Main.f)\n@LOOP\n(LOOP)\n@Lib.g\n0;JMP\n(Main.end)\n");
+    let parsed = Object::parse(&original.to_string()).unwrap();
+    assert_eq!(parsed.words, original.words);
+    assert_eq!(parsed.exports, original.exports);
+    assert_eq!(parsed.exports["Main.end"], 3);
+    assert_eq!(parsed.relocations, [0]);
+    assert_eq!(parsed.references, [(1, "Lib.g".to_string())]);
+}
+
+#[test]
+fn offsets_outside_of_the_code_are_rejected() {
+    let code = "code\n0000000000000000\n";
+    for (records, error) in [
+        (
+            "export Lib.f 2\n",
+            "`Lib.f` at offset 2 is outside of the code",
+        ),
+        ("reloc 1\n", "offset 1 is outside of the code"),
+        ("ref 5 Lib.f\n", "offset 5 is outside of the code"),
+    ] {
+        let contents = format!("hobj\n{records}{code}");
+        assert_eq!(Object::parse(&contents).err().unwrap(), error);
+    }
+    // A label right after the last word is fine
+    assert!(Object::parse(&format!("hobj\nexport Lib.end 1\n{code}")).is_ok());
+}
@@ -0,0 +1,75 @@
+use asm::disassembler::decode;
+use asm::{assemble_with, Options};
+
+// The optimized program, disassembled
+fn optimize(source: &str) -> Vec<String> {
+    let options = Options {
+        optimize: true,
+        ..Default::default()
+    };
+    let program = assemble_with(source, &options).unwrap();
+    program.words.iter().map(|w| decode(*w).unwrap()).collect()
+}
+
+#[test]
+fn redundant_loads_are_removed() {
+    assert_eq!(optimize("@5\nD=M\n@5\nM=D+1\n"), ["@5", "D=M", "M=D+1"]);
+}
+
+#[test]
+fn loads_after_a_label_or_a_write_to_a_are_kept() {
+    // `(L)` may be reached with anything in A
+    assert_eq!(
+        optimize("@5\nD=M\n(L)\n@5\nM=D+1\n@L\nD;JGT\n"),
+        ["@5", "D=M", "@5", "M=D+1", "@2", "D;JGT"]
+    );
+    assert_eq!(optimize("@5\nA=M\n@5\nD=M\n"), ["@5", "A=M", "@5", "D=M"]);
+}
+
+#[test]
+fn jumps_to_the_next_label_are_removed() {
+    assert_eq!(optimize("@NEXT\nD;JGT\n(NEXT)\nD=1\n"), ["D=1"]);
+    assert_eq!(optimize("@NEXT\n0;JMP\n(OTHER)\n(NEXT)\nD=1\n"), ["D=1"]);
+}
+
+#[test]
+fn jumps_over_code_or_writing_registers_are_kept() {
+    assert_eq!(
+        optimize("@L\nD;JGT\nD=0\n(L)\nM=D\n"),
+        ["@3", "D;JGT", "D=0", "M=D"]
+    );
+    // The jump also writes D
+    assert_eq!(optimize("@L\nD=D-1;JGT\n(L)\n"), ["@2", "D=D-1;JGT"]);
+}
+
+#[test]
+fn round_trips_through_memory_are_removed() {
+    assert_eq!(optimize("@5\nD=M\nM=D\n"), ["@5", "D=M"]);
+    assert_eq!(optimize("@5\nM=D\nD=M\n"), ["@5", "M=D"]);
+}
+
+#[test]
+fn round_trips_across_a_label_are_kept() {
+    assert_eq!(
+        optimize("@5\nD=M\n(L)\nM=D\n@L\nD;JGT\n"),
+        ["@5", "D=M", "M=D", "@2", "D;JGT"]
+    );
+    // A changes in between
+    assert_eq!(optimize("@5\nD=M\n@6\nM=D\n"), ["@5", "D=M", "@6", "M=D"]);
+}
+
+#[test]
+fn code_after_an_unconditional_jump_is_removed() {
+    assert_eq!(
+        optimize("@START\n0;JMP\nD=1\nM=1\n(START)\nD=0\n(END)\n@END\n0;JMP\n"),
+        ["D=0", "@1", "0;JMP"]
+    );
+}
+
+#[test]
+fn code_after_a_jump_that_a_label_makes_reachable_is_kept() {
+    assert_eq!(
+        optimize("@END\n0;JMP\n(MID)\nD=1\n(END)\n@MID\n0;JMP\n"),
+        ["@3", "0;JMP", "D=1", "@2", "0;JMP"]
+    );
+}
@@ -0,0 +1,77 @@
+use asm::output::Format;
+
+fn encode(format: Format, words: &[u16]) -> String {
+    String::from_utf8(format.encode(words, "Prog")).unwrap()
+}
+
+#[test]
+fn raw_images_in_both_byte_orders() {
+    let words = [0x0010, 0xEFC8];
+    assert_eq!(Format::RawLe.encode(&words, ""), [0x10, 0x00, 0xC8, 0xEF]);
+    assert_eq!(Format::RawBe.encode(&words, ""), [0x00, 0x10, 0xEF, 0xC8]);
+    assert!(Format::RawLe.encode(&[], "").is_empty());
+}
+
+#[test]
+fn intel_hex_records() {
+    assert_eq!(
+        encode(Format::IntelHex, &[0x0010, 0xEFC8]),
+        ":040000000010EFC835\n:00000001FF\n"
+    );
+
+    // 16 bytes per record, addresses in bytes
+    let words: Vec<u16> = (0..9).collect();
+    assert_eq!(
+        encode(Format::IntelHex, &words),
+        "\
+:1000000000000001000200030004000500060007D4
+:020010000008E6
+:00000001FF
+"
+    );
+    assert_eq!(encode(Format::IntelHex, &[]), ":00000001FF\n");
+}
+
+#[test]
+fn intel_hex_checksums_make_records_sum_to_zero() {
+    let words: Vec<u16> = (0..100u16).map(|i| i.wrapping_mul(0x1357)).collect();
+    for record in encode(Format::IntelHex, &words).lines() {
+        let hex = record.strip_prefix(':').unwrap();
+        let sum = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .fold(0u8, u8::wrapping_add);
+        assert_eq!(sum, 0, "{record}");
+    }
+}
+
+#[test]
+fn logisim_runs_of_four_or_more_are_encoded() {
+    let words = [5, 5, 5, 5, 1, 2, 2, 2, 0xEFC8, 0, 0, 0, 0, 0, 7, 8, 9, 10];
+    assert_eq!(
+        encode(Format::Logisim, &words),
+        "v2.0 raw\n4*5 1 2 2 2 efc8 5*0 7\n8 9 a\n"
+    );
+    assert_eq!(encode(Format::Logisim, &[]), "v2.0 raw\n");
+}
+
+#[test]
+fn array_literals() {
+    assert_eq!(
+        encode(Format::C, &[0x0010, 0xEFC8]),
+        "#include <stdint.h>\n\nconst uint16_t PROG_ROM[2] = {\n    0x0010, 0xEFC8,\n};\n"
+    );
+    assert_eq!(
+        encode(Format::Rust, &[0x0010]),
+        "pub static PROG_ROM: [u16; 1] = [\n    0x0010,\n];\n"
+    );
+    assert_eq!(Format::Hack.encode(&[5], ""), b"0000000000000101\n");
+}
+
+#[test]
+fn an_empty_program_is_a_valid_c_array() {
+    assert_eq!(
+        encode(Format::C, &[]),
+        "#include <stdint.h>\n\nconst uint16_t PROG_ROM[1] = {\n    0x0000,\n};\n"
+    );
+}
@@ -0,0 +1,65 @@
+use asm::assemble;
+use asm::disassembler::SymbolFile;
+
+const SOURCE: &str = "\
+@i
+M=1   // i = 1
+(LOOP)
+(AGAIN)
+@LOOP
+0;JMP
+(END)
+";
+
+#[test]
+fn listing_shows_address_word_and_source() {
+    let listing = assemble(SOURCE).unwrap().listing();
+    assert_eq!(
+        listing,
+        "\
+ROM    binary            hex   source
+00000  0000000000010000  0010  @i
+00001  1110111111001000  EFC8  M=1   // i = 1
+                               (AGAIN)
+                               (LOOP)
+00002  0000000000000010  0002  @LOOP
+00003  1110101010000111  EA87  0;JMP
+                               (END)
+"
+    );
+}
+
+#[test]
+fn symbol_file_lists_labels_then_variables() {
+    let symbols = assemble(SOURCE).unwrap().symbol_file();
+    assert_eq!(
+        symbols,
+        "label AGAIN 2\nlabel LOOP 2\nlabel END 4\nvariable i 16\n"
+    );
+
+    // `dis` and `edb` read it back
+    let file = SymbolFile::parse(&symbols).unwrap();
+    assert_eq!(file.labels[&2], ["AGAIN", "LOOP"]);
+    assert_eq!(file.variables[&16], ["i"]);
+}
+
+#[test]
+fn flags_write_the_files_next_to_the_hack_file() {
+    let dir = std::env::temp_dir().join(format!("asm-files-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("Loop.asm");
+    std::fs::write(&path, SOURCE).unwrap();
+
+    let status = std::process::Command::new(env!("CARGO_BIN_EXE_asm"))
+        .args(["--lst", "--sym"])
+        .arg(&path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let program = assemble(SOURCE).unwrap();
+    let read = |ext: &str| std::fs::read_to_string(path.with_extension(ext)).unwrap();
+    assert_eq!(read("lst"), program.listing());
+    assert_eq!(read("sym"), program.symbol_file());
+    assert_eq!(read("hack").lines().count(), 4);
+    std::fs::remove_dir_all(dir).unwrap();
+}
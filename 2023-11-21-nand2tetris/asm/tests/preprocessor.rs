@@ -0,0 +1,97 @@
+use std::fs;
+
+use asm::{assemble, assemble_with, Options};
+
+#[test]
+fn nested_includes_are_relative_to_the_including_file() {
+    let dir = std::env::temp_dir().join(format!("asm-include-{}", std::process::id()));
+    fs::create_dir_all(dir.join("lib/util")).unwrap();
+    fs::write(dir.join("lib/a.asm"), ".include \"util/b.asm\"\n@1\n").unwrap();
+    fs::write(dir.join("lib/util/b.asm"), "@2\n").unwrap();
+
+    let options = Options {
+        include_dir: Some(dir.clone()),
+        ..Default::default()
+    };
+    let program = assemble_with(".include \"lib/a.asm\"\n@3\n", &options).unwrap();
+    assert_eq!(program.words, [2, 1, 3]);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn macros_cannot_be_named_like_registers() {
+    for name in ["M", "D", "AM", "ADM"] {
+        let source = format!(".macro {name}\n@0\n.endm\nM = D\n");
+        let error = assemble(&source).err().unwrap().to_string();
+        assert!(error.contains("is a register name"), "{error}");
+    }
+
+    let program = assemble(".macro MOV\nD=M\n.endm\nMOV\nM = D\n").unwrap();
+    assert_eq!(program.words, [0xFC10, 0xE308]);
+}
+
+#[test]
+fn a_file_including_itself_twice_is_a_cycle() {
+    let dir = std::env::temp_dir().join(format!("asm-cycle-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("a.asm"),
+        ".include \"b.asm\"\n.include \"b.asm\"\n",
+    )
+    .unwrap();
+    fs::write(dir.join("b.asm"), "@1\n.include \"a.asm\"\n").unwrap();
+
+    let options = Options {
+        include_dir: Some(dir.clone()),
+        ..Default::default()
+    };
+    let diagnostics = assemble_with(".include \"a.asm\"\n", &options)
+        .err()
+        .unwrap();
+    let messages: Vec<&str> = diagnostics
+        .iter()
+        .map(|d| d.error.message.as_str())
+        .collect();
+    assert_eq!(messages.len(), 2);
+    let a = fs::canonicalize(dir.join("a.asm")).unwrap();
+    let b = fs::canonicalize(dir.join("b.asm")).unwrap();
+    let cycle = format!(
+        "`a.asm` is included recursively: {} -> {} -> {}",
+        a.display(),
+        b.display(),
+        a.display()
+    );
+    assert!(messages.iter().all(|m| *m == cycle), "{messages:?}");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn a_macro_invoking_itself_twice_is_a_cycle() {
+    let source = "\
+.macro TWICE
+TWICE
+TWICE
+.endm
+.macro PING
+PONG
+.endm
+.macro PONG
+PING
+.endm
+TWICE
+PING
+";
+    let diagnostics = assemble(source).err().unwrap();
+    let messages: Vec<&str> = diagnostics
+        .iter()
+        .map(|d| d.error.message.as_str())
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            "macro `TWICE` expands recursively: TWICE -> TWICE",
+            "macro `TWICE` expands recursively: TWICE -> TWICE",
+            "macro `PING` expands recursively: PING -> PONG -> PING",
+        ]
+    );
+}
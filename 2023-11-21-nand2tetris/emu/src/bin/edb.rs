@@ -0,0 +1,202 @@
+use std::env;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+use anyhow::{bail, Result};
+
+use emu::debugger::{Debugger, Event};
+use emu::loader::load_with_symbols;
+
+const USAGE: &str = "Usage: edb file";
+
+const HELP: &str = "\
+break LOC      stop before executing LOC, a ROM address or a label
+delete LOC     remove a breakpoint
+watch ADDR     stop when RAM[ADDR] changes. ADDR may be a number, SP, LCL, R13... or a variable
+unwatch ADDR   remove a watchpoint
+info           list breakpoints and watchpoints
+step [N]       execute N instructions, 1 by default
+continue       run until a breakpoint, a watchpoint or the program halts
+disas [N]      disassemble N instructions around PC, 5 by default
+regs           show A, D and PC
+print ADDR[..END]
+               show RAM[ADDR] or RAM[ADDR..END]
+stack          show the VM call stack
+reset          set A, D and PC to 0. RAM is kept
+quit
+An empty line repeats the previous command.";
+
+// Instructions `continue` runs before giving control back, so that a program that
+// never halts does not hang the debugger
+const CONTINUE_LIMIT: u64 = 100_000_000;
+
+// Interactive debugger for Hack programs. Symbols and source locations come from
+// the assembler for `.asm` files and from the `.sym` and `.map` files next to
+// `.hack` files.
+fn main() -> Result<()> {
+    let arguments: Vec<String> = env::args().skip(1).collect();
+    let [file] = arguments.as_slice() else {
+        bail!(USAGE);
+    };
+
+    let (words, symbols) = load_with_symbols(Path::new(file))?;
+    let mut debugger = Debugger::new(&words, symbols);
+    println!(
+        "Loaded {} instructions. Type `help` for commands.",
+        words.len()
+    );
+
+    let stdin = io::stdin();
+    let mut previous = String::new();
+    loop {
+        print!("(edb) ");
+        io::stdout().flush()?;
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            break;
+        }
+        let line = match line.trim() {
+            "" => previous.clone(),
+            line => line.to_string(),
+        };
+        previous = line.clone();
+
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            continue;
+        };
+        let argument = words.next();
+        match (command, argument) {
+            ("b" | "break", Some(loc)) => match debugger.rom_address(loc) {
+                Some(address) => {
+                    debugger.breakpoints.insert(address);
+                    println!("Breakpoint at {address}");
+                }
+                None => println!("Unknown ROM address or label {loc}"),
+            },
+            ("d" | "delete", Some(loc)) => match debugger.rom_address(loc) {
+                Some(address) if debugger.breakpoints.remove(&address) => {}
+                _ => println!("No breakpoint at {loc}"),
+            },
+            ("w" | "watch", Some(name)) => match debugger.ram_address(name) {
+                Some(address) => {
+                    debugger.watch(address);
+                    println!("Watching RAM[{address}]");
+                }
+                None => println!("Unknown RAM address or symbol {name}"),
+            },
+            ("unwatch", Some(name)) => match debugger.ram_address(name) {
+                Some(address) if debugger.watches.remove(&address).is_some() => {}
+                _ => println!("{name} is not watched"),
+            },
+            ("info", None) => {
+                for address in &debugger.breakpoints {
+                    let name = debugger.function_at(*address).unwrap_or("?");
+                    println!("breakpoint {address} in {name}");
+                }
+                for (address, value) in &debugger.watches {
+                    println!("watch RAM[{address}] = {}", *value as i16);
+                }
+            }
+            ("s" | "step", n) => {
+                let Some(n) = parse_count(n, 1) else {
+                    println!("Invalid count");
+                    continue;
+                };
+                let event = debugger.run(n);
+                report(&debugger, event);
+            }
+            ("c" | "continue", None) => {
+                let event = debugger.run(CONTINUE_LIMIT);
+                report(&debugger, event);
+            }
+            ("x" | "disas", n) => {
+                let Some(n) = parse_count(n, 5) else {
+                    println!("Invalid count");
+                    continue;
+                };
+                let n = n.min(u16::MAX as u64) as u16;
+                print!("{}", debugger.disassemble(n / 2, n - n / 2));
+            }
+            ("r" | "regs", None) => {
+                let computer = &debugger.computer;
+                println!(
+                    "A = {}, D = {}, PC = {}, cycles = {}",
+                    computer.a() as i16,
+                    computer.d() as i16,
+                    computer.pc(),
+                    computer.cycles()
+                );
+            }
+            ("p" | "print", Some(range)) => {
+                let (start, end) = match range.split_once("..") {
+                    Some((start, end)) => (debugger.ram_address(start), debugger.ram_address(end)),
+                    None => {
+                        let address = debugger.ram_address(range);
+                        (address, address.map(|a| a + 1))
+                    }
+                };
+                let (Some(start), Some(end)) = (start, end) else {
+                    println!("Invalid RAM range {range}");
+                    continue;
+                };
+                for address in start..end {
+                    println!(
+                        "RAM[{address}] = {}",
+                        debugger.computer.ram()[address] as i16
+                    );
+                }
+            }
+            ("bt" | "stack", None) => {
+                let frames = debugger.stack();
+                if frames.is_empty() {
+                    println!("No VM frames: LCL and ARG do not point into the stack");
+                }
+                for (i, frame) in frames.iter().enumerate() {
+                    println!(
+                        "#{i} {} at {}  ARG = {}, LCL = {}",
+                        frame.function, frame.pc, frame.arg, frame.lcl
+                    );
+                    if let Some(location) = debugger.location(frame.pc) {
+                        println!("   in {location}");
+                    }
+                    println!("   args:  {}", values(&frame.args));
+                    println!("   stack: {}", values(&frame.stack));
+                }
+            }
+            ("reset", None) => debugger.computer.reset(),
+            ("h" | "help", None) => println!("{HELP}"),
+            ("q" | "quit", None) => break,
+            _ => println!("Unknown command `{line}`. Type `help` for commands."),
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_count(s: Option<&str>, default: u64) -> Option<u64> {
+    s.map_or(Some(default), |s| s.parse().ok())
+}
+
+fn values(words: &[u16]) -> String {
+    let values: Vec<String> = words.iter().map(|w| (*w as i16).to_string()).collect();
+    values.join(" ")
+}
+
+fn report(debugger: &Debugger, event: Event) {
+    match event {
+        Event::Breakpoint(address) => println!("Breakpoint at {address}"),
+        Event::Watch { address, old, new } => {
+            println!(
+                "RAM[{address}] changed from {} to {}",
+                old as i16, new as i16
+            )
+        }
+        Event::Halted => println!("Halted after {} cycles", debugger.computer.cycles()),
+        Event::Done => {}
+    }
+    if let Some(location) = debugger.location(debugger.computer.pc()) {
+        println!("In {location}");
+    }
+    print!("{}", debugger.disassemble(0, 0));
+}
@@ -0,0 +1,345 @@
+pub const ROM_SIZE: usize = 32768;
+pub const RAM_SIZE: usize = 32768;
+
+pub const SCREEN: usize = 16384;
+pub const KBD: usize = 24576;
+
+/// Why `Computer::run` returned
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Stop {
+    /// The program reached a halt loop such as `(END) @END 0;JMP`
+    Halted,
+    /// The requested number of cycles was executed
+    CycleLimit,
+}
+
+/// The Hack computer: 32K ROM, 32K RAM (with `SCREEN` and `KBD` mapped into it) and the
+/// A, D and PC registers
+pub struct Computer {
+    rom: Box<[u16]>,
+    ops: Box<[Op]>,
+    ram: Box<[u16]>,
+    regs: Registers,
+    cycles: u64,
+}
+
+// Kept apart from RAM so that `run` can hold them in locals: RAM writes could
+// otherwise alias them and force a reload after every instruction
+#[derive(Clone, Copy, Default)]
+struct Registers {
+    a: u16,
+    d: u16,
+    pc: u16,
+}
+
+impl Computer {
+    /// Panics if `program` does not fit in ROM, which `loader::load` checks
+    pub fn new(program: &[u16]) -> Computer {
+        assert!(program.len() <= ROM_SIZE, "Program does not fit in ROM");
+
+        let mut rom = vec![0; ROM_SIZE].into_boxed_slice();
+        rom[..program.len()].copy_from_slice(program);
+        Computer {
+            ops: rom.iter().map(|&ins| decode(ins)).collect(),
+            rom,
+            ram: vec![0; RAM_SIZE].into_boxed_slice(),
+            regs: Registers::default(),
+            cycles: 0,
+        }
+    }
+
+    /// Reset the registers, like pressing the reset button. RAM is kept.
+    pub fn reset(&mut self) {
+        self.regs = Registers::default();
+        self.cycles = 0;
+    }
+
+    pub fn a(&self) -> u16 {
+        self.regs.a
+    }
+
+    pub fn d(&self) -> u16 {
+        self.regs.d
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.regs.pc
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    pub fn set_a(&mut self, value: u16) {
+        self.regs.a = value;
+    }
+
+    pub fn set_d(&mut self, value: u16) {
+        self.regs.d = value;
+    }
+
+    pub fn set_pc(&mut self, value: u16) {
+        self.regs.pc = value;
+    }
+
+    pub fn rom(&self) -> &[u16] {
+        &self.rom
+    }
+
+    pub fn ram(&self) -> &[u16] {
+        &self.ram
+    }
+
+    pub fn ram_mut(&mut self) -> &mut [u16] {
+        &mut self.ram
+    }
+
+    /// Set the key currently pressed, 0 for none
+    pub fn set_keyboard(&mut self, key: u16) {
+        self.ram[KBD] = key;
+    }
+
+    /// Execute the instruction at PC
+    pub fn step(&mut self) {
+        self.cycles += 1;
+        execute(
+            self.ops[self.regs.pc as usize],
+            &mut self.regs,
+            &mut self.ram,
+        );
+    }
+
+    /// Whether PC sits in a loop that can never be left and changes nothing, e.g.
+    /// `(END) @END 0;JMP`. A loop such as `@5 M=M+1;JMP` keeps writing RAM, so it runs on.
+    pub fn is_halted(&self) -> bool {
+        halted(&self.ops, self.regs)
+    }
+
+    /// Run until the program halts or `max_cycles` instructions have been executed
+    pub fn run(&mut self, max_cycles: u64) -> Stop {
+        let mut regs = self.regs;
+        let mut executed = 0;
+        while executed < max_cycles && !halted(&self.ops, regs) {
+            execute(self.ops[regs.pc as usize], &mut regs, &mut self.ram);
+            executed += 1;
+        }
+        self.regs = regs;
+        self.cycles += executed;
+
+        if self.is_halted() {
+            Stop::Halted
+        } else {
+            Stop::CycleLimit
+        }
+    }
+}
+
+// Execute one decoded instruction. Inlined so that `run` keeps the registers in
+// machine registers.
+#[inline(always)]
+fn execute(op: Op, regs: &mut Registers, ram: &mut [u16]) {
+    let (comp, dest, jump) = match op {
+        Op::Load(value) => {
+            regs.a = value;
+            regs.pc = (regs.pc + 1) & 0x7FFF;
+            return;
+        }
+        Op::Compute { comp, dest, jump } => (comp, dest, jump),
+    };
+
+    let address = regs.a as usize & 0x7FFF;
+    let (x, a) = (regs.d, regs.a);
+    let out = match comp {
+        Comp::Zero => 0,
+        Comp::One => 1,
+        Comp::MinusOne => 0xFFFF,
+        Comp::D => x,
+        Comp::A => a,
+        Comp::M => ram[address],
+        Comp::NotD => !x,
+        Comp::NotA => !a,
+        Comp::NotM => !ram[address],
+        Comp::NegD => x.wrapping_neg(),
+        Comp::NegA => a.wrapping_neg(),
+        Comp::NegM => ram[address].wrapping_neg(),
+        Comp::DPlus1 => x.wrapping_add(1),
+        Comp::APlus1 => a.wrapping_add(1),
+        Comp::MPlus1 => ram[address].wrapping_add(1),
+        Comp::DMinus1 => x.wrapping_sub(1),
+        Comp::AMinus1 => a.wrapping_sub(1),
+        Comp::MMinus1 => ram[address].wrapping_sub(1),
+        Comp::DPlusA => x.wrapping_add(a),
+        Comp::DPlusM => x.wrapping_add(ram[address]),
+        Comp::DMinusA => x.wrapping_sub(a),
+        Comp::DMinusM => x.wrapping_sub(ram[address]),
+        Comp::AMinusD => a.wrapping_sub(x),
+        Comp::MMinusD => ram[address].wrapping_sub(x),
+        Comp::DAndA => x & a,
+        Comp::DAndM => x & ram[address],
+        Comp::DOrA => x | a,
+        Comp::DOrM => x | ram[address],
+        Comp::Alu { c, m } => alu(x, if m { ram[address] } else { a }, c),
+    };
+
+    if dest & DEST_M != 0 {
+        ram[address] = out;
+    }
+    if dest & DEST_D != 0 {
+        regs.d = out;
+    }
+    // A is written last: M and the jump use the old value
+    if dest & DEST_A != 0 {
+        regs.a = out;
+    }
+
+    // Bit 2 for negative, 1 for zero and 0 for positive, as in the jump field
+    let sign = match out as i16 {
+        i16::MIN..=-1 => 0b100,
+        0 => 0b010,
+        _ => 0b001,
+    };
+    regs.pc = if jump & sign != 0 {
+        a & 0x7FFF
+    } else {
+        (regs.pc + 1) & 0x7FFF
+    };
+}
+
+// See `Computer::is_halted`
+fn halted(ops: &[Op], regs: Registers) -> bool {
+    let pc = regs.pc as usize;
+    match ops[pc] {
+        // `@pc` followed by an unconditional jump that writes neither D nor M
+        Op::Load(value) => {
+            value == pc as u16
+                && matches!(
+                    ops[(pc + 1) % ROM_SIZE],
+                    Op::Compute { jump: 0b111, dest, .. } if dest & (DEST_D | DEST_M) == 0
+                )
+        }
+        // An unconditional jump to itself that writes no register
+        Op::Compute { jump, dest, .. } => {
+            jump == 0b111 && dest & (DEST_A | DEST_D | DEST_M) == 0 && regs.a == pc as u16
+        }
+    }
+}
+
+/// The Hack ALU. `c` holds the `zx nx zy ny f no` control bits in its lowest 6 bits.
+pub fn alu(x: u16, y: u16, c: u16) -> u16 {
+    let x = if c & 0b100000 != 0 { 0 } else { x };
+    let x = if c & 0b010000 != 0 { !x } else { x };
+    let y = if c & 0b001000 != 0 { 0 } else { y };
+    let y = if c & 0b000100 != 0 { !y } else { y };
+    let out = if c & 0b000010 != 0 {
+        x.wrapping_add(y)
+    } else {
+        x & y
+    };
+    if c & 0b000001 != 0 {
+        !out
+    } else {
+        out
+    }
+}
+
+// Destination bits of a C-instruction
+const DEST_A: u8 = 0b100;
+const DEST_D: u8 = 0b010;
+const DEST_M: u8 = 0b001;
+
+/// A ROM word decoded once when the program is loaded, so that `step` dispatches on
+/// the computation instead of decoding bits
+#[derive(Clone, Copy)]
+enum Op {
+    /// `@value`
+    Load(u16),
+    Compute {
+        comp: Comp,
+        dest: u8,
+        jump: u8,
+    },
+}
+
+/// The 28 computations of the book, and the ALU for the other control bits
+#[derive(Clone, Copy)]
+enum Comp {
+    Zero,
+    One,
+    MinusOne,
+    D,
+    A,
+    M,
+    NotD,
+    NotA,
+    NotM,
+    NegD,
+    NegA,
+    NegM,
+    DPlus1,
+    APlus1,
+    MPlus1,
+    DMinus1,
+    AMinus1,
+    MMinus1,
+    DPlusA,
+    DPlusM,
+    DMinusA,
+    DMinusM,
+    AMinusD,
+    MMinusD,
+    DAndA,
+    DAndM,
+    DOrA,
+    DOrM,
+    /// `c` holds the control bits and `m` selects M instead of A
+    Alu {
+        c: u16,
+        m: bool,
+    },
+}
+
+fn decode(ins: u16) -> Op {
+    if ins & 0x8000 == 0 {
+        return Op::Load(ins);
+    }
+
+    let comp = match (ins >> 6) & 0b111_1111 {
+        0b010_1010 | 0b110_1010 => Comp::Zero,
+        0b011_1111 | 0b111_1111 => Comp::One,
+        0b011_1010 | 0b111_1010 => Comp::MinusOne,
+        0b000_1100 | 0b100_1100 => Comp::D,
+        0b011_0000 => Comp::A,
+        0b111_0000 => Comp::M,
+        0b000_1101 | 0b100_1101 => Comp::NotD,
+        0b011_0001 => Comp::NotA,
+        0b111_0001 => Comp::NotM,
+        0b000_1111 | 0b100_1111 => Comp::NegD,
+        0b011_0011 => Comp::NegA,
+        0b111_0011 => Comp::NegM,
+        0b001_1111 | 0b101_1111 => Comp::DPlus1,
+        0b011_0111 => Comp::APlus1,
+        0b111_0111 => Comp::MPlus1,
+        0b000_1110 | 0b100_1110 => Comp::DMinus1,
+        0b011_0010 => Comp::AMinus1,
+        0b111_0010 => Comp::MMinus1,
+        0b000_0010 => Comp::DPlusA,
+        0b100_0010 => Comp::DPlusM,
+        0b001_0011 => Comp::DMinusA,
+        0b101_0011 => Comp::DMinusM,
+        0b000_0111 => Comp::AMinusD,
+        0b100_0111 => Comp::MMinusD,
+        0b000_0000 => Comp::DAndA,
+        0b100_0000 => Comp::DAndM,
+        0b001_0101 => Comp::DOrA,
+        0b101_0101 => Comp::DOrM,
+        c => Comp::Alu {
+            c: c & 0b11_1111,
+            m: c & 0b100_0000 != 0,
+        },
+    };
+    Op::Compute {
+        comp,
+        dest: ((ins >> 3) & 0b111) as u8,
+        jump: (ins & 0b111) as u8,
+    }
+}
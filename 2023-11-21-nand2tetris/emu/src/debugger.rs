@@ -0,0 +1,253 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use asm::disassembler::{decode, is_jump, uses_memory};
+use asm::symbol_tables::SymbolsTable;
+
+use crate::cpu::{Computer, RAM_SIZE, ROM_SIZE};
+use crate::loader::Symbols;
+
+// RAM addresses of the VM pointers
+const SP: usize = 0;
+const LCL: usize = 1;
+const ARG: usize = 2;
+
+// The VM stack lives in RAM[256..2048]
+const STACK_START: u16 = 256;
+const STACK_END: u16 = 2048;
+
+/// Why `Debugger::run` returned
+pub enum Event {
+    /// PC reached a breakpoint, before executing it
+    Breakpoint(u16),
+    /// A watched RAM word changed
+    Watch {
+        address: usize,
+        old: u16,
+        new: u16,
+    },
+    Halted,
+    /// The requested number of instructions was executed
+    Done,
+}
+
+/// One function activation of the VM calling convention. `call` pushes the return address
+/// and the caller's LCL, ARG, THIS and THAT, so that the saved values sit right below LCL.
+pub struct Frame {
+    pub function: String,
+    /// PC in this function: the current one for the innermost frame, the return address
+    /// for the others
+    pub pc: u16,
+    pub arg: u16,
+    pub lcl: u16,
+    pub args: Vec<u16>,
+    /// Local variables followed by the working stack
+    pub stack: Vec<u16>,
+}
+
+pub struct Debugger {
+    pub computer: Computer,
+    pub symbols: Symbols,
+    pub breakpoints: BTreeSet<u16>,
+    /// Watched addresses with their last seen value
+    pub watches: BTreeMap<usize, u16>,
+    // Label names of every ROM address
+    names: BTreeMap<u16, Vec<String>>,
+    // Number of instructions of the program
+    size: usize,
+}
+
+impl Debugger {
+    pub fn new(program: &[u16], symbols: Symbols) -> Debugger {
+        let mut names: BTreeMap<u16, Vec<String>> = BTreeMap::new();
+        for (name, address) in &symbols.labels {
+            names.entry(*address).or_default().push(name.clone());
+        }
+        Debugger {
+            computer: Computer::new(program),
+            symbols,
+            breakpoints: BTreeSet::new(),
+            watches: BTreeMap::new(),
+            names,
+            size: program.len(),
+        }
+    }
+
+    /// A ROM address given as a number or a label
+    pub fn rom_address(&self, s: &str) -> Option<u16> {
+        let address = match s.parse::<u16>() {
+            Ok(address) => address,
+            Err(_) => *self.symbols.labels.get(s)?,
+        };
+        ((address as usize) < ROM_SIZE).then_some(address)
+    }
+
+    /// A RAM address given as a number, a predefined symbol such as `SP` or a variable
+    pub fn ram_address(&self, s: &str) -> Option<usize> {
+        let address = match s.parse::<usize>() {
+            Ok(address) => address,
+            Err(_) => match SymbolsTable::new().get(s) {
+                Some(address) => *address as usize,
+                None => *self.symbols.variables.get(s)? as usize,
+            },
+        };
+        (address < RAM_SIZE).then_some(address)
+    }
+
+    pub fn watch(&mut self, address: usize) {
+        self.watches.insert(address, self.computer.ram()[address]);
+    }
+
+    /// Execute at most `max` instructions, stopping early at a breakpoint, a change of
+    /// a watched word or when the program halts. A breakpoint at the current PC does not
+    /// stop the first instruction, so that `run` can resume from it.
+    pub fn run(&mut self, max: u64) -> Event {
+        for i in 0..max {
+            let pc = self.computer.pc();
+            if i > 0 && self.breakpoints.contains(&pc) {
+                return Event::Breakpoint(pc);
+            }
+            if self.computer.is_halted() {
+                return Event::Halted;
+            }
+
+            self.computer.step();
+
+            let ram = self.computer.ram();
+            for (&address, old) in self.watches.iter_mut() {
+                let new = ram[address];
+                if new != *old {
+                    let event = Event::Watch {
+                        address,
+                        old: *old,
+                        new,
+                    };
+                    *old = new;
+                    return event;
+                }
+            }
+        }
+        Event::Done
+    }
+
+    /// The label `address` is best known by. A label equal to another one followed by
+    /// `.` or `$` is local to it, e.g. `Main.loop$LOOP` or `Sys.init$$ret0`.
+    pub fn function_at(&self, address: u16) -> Option<&str> {
+        let is_local = |name: &str| {
+            self.symbols.labels.keys().any(|other| {
+                name.strip_prefix(other.as_str())
+                    .is_some_and(|rest| rest.starts_with(['.', '$']))
+            })
+        };
+        self.names
+            .range(..=address)
+            .rev()
+            .flat_map(|(_, names)| names)
+            .find(|name| !is_local(name))
+            .map(String::as_str)
+    }
+
+    /// Where the instruction at `address` comes from, e.g.
+    /// `Main.fib: push argument 0 (Fib.asm:16)`, if the source map is known
+    pub fn location(&self, address: u16) -> Option<String> {
+        let location = self.symbols.source_map.get(address as usize)?;
+        let position = format!("{}:{}", location.file, location.line);
+        Some(match (&location.function, &location.vm_command) {
+            (Some(function), Some(command)) => format!("{function}: {command} ({position})"),
+            (None, Some(command)) => format!("{command} ({position})"),
+            _ => position,
+        })
+    }
+
+    /// `before` instructions before PC and `after` after it, with labels, breakpoints (`*`)
+    /// and the PC (`=>`) marked
+    pub fn disassemble(&self, before: u16, after: u16) -> String {
+        let pc = self.computer.pc();
+        let rom = self.computer.rom();
+        let start = pc.saturating_sub(before);
+        let end = (pc as usize + after as usize + 1).min(self.size.max(pc as usize + 1));
+
+        let variables: BTreeMap<u16, &str> = self
+            .symbols
+            .variables
+            .iter()
+            .map(|(name, address)| (*address, name.as_str()))
+            .collect();
+
+        let mut out = String::new();
+        for address in start as usize..end {
+            for name in self.names.get(&(address as u16)).into_iter().flatten() {
+                writeln!(out, "{:11}({name})", "").unwrap();
+            }
+
+            let word = rom[address];
+            let next = rom.get(address + 1).copied().unwrap_or(0);
+            let mnemonic = decode(word).unwrap_or_else(|| format!("?? {word:016b}"));
+            let name = if word & 0x8000 != 0 {
+                None
+            } else if is_jump(next) {
+                self.names
+                    .get(&word)
+                    .and_then(|n| n.first())
+                    .map(String::as_str)
+            } else if uses_memory(next) {
+                variables.get(&word).copied()
+            } else {
+                None
+            };
+
+            let marker = if address == pc as usize { "=>" } else { "" };
+            let breakpoint = if self.breakpoints.contains(&(address as u16)) {
+                '*'
+            } else {
+                ' '
+            };
+            write!(out, "{marker:2}{breakpoint}{address:05}   {mnemonic}").unwrap();
+            if let Some(name) = name {
+                write!(out, "  // {name}").unwrap();
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Walk the VM frames from the innermost one, following the saved LCL pointers.
+    /// Empty when LCL and ARG do not look like a VM stack, e.g. in a program not
+    /// produced by the VM translator.
+    pub fn stack(&self) -> Vec<Frame> {
+        let ram = self.computer.ram();
+        let mut frames = Vec::new();
+
+        let mut pc = self.computer.pc();
+        let mut lcl = ram[LCL];
+        let mut arg = ram[ARG];
+        let mut top = ram[SP];
+        // Bounded in case the saved pointers are corrupted
+        while frames.len() < 256 {
+            let valid = STACK_START <= arg
+                && arg.saturating_add(5) <= lcl
+                && lcl <= top
+                && top <= STACK_END
+                && (pc as usize) < self.size;
+            if !valid {
+                break;
+            }
+
+            frames.push(Frame {
+                function: self.function_at(pc).unwrap_or("?").to_string(),
+                pc,
+                arg,
+                lcl,
+                args: ram[arg as usize..lcl as usize - 5].to_vec(),
+                stack: ram[lcl as usize..top as usize].to_vec(),
+            });
+
+            let saved = lcl as usize - 5;
+            top = arg;
+            pc = ram[saved];
+            lcl = ram[saved + 1];
+            arg = ram[saved + 2];
+        }
+        frames
+    }
+}
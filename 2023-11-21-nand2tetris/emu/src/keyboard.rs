@@ -0,0 +1,84 @@
+use anyhow::{bail, Context, Result};
+
+/// Values written to `KBD` over time. Each line of a timeline script is
+/// `cycle key`, where `key` is a number, a single character, a key name such as
+/// `ENTER` or `LEFT`, or `NONE` to release. Lines starting with `#` are comments.
+pub struct Timeline(Vec<(u64, u16)>);
+
+const KEYS: [(&str, u16); 16] = [
+    ("NONE", 0),
+    ("SPACE", 32),
+    ("ENTER", 128),
+    ("NEWLINE", 128),
+    ("BACKSPACE", 129),
+    ("LEFT", 130),
+    ("UP", 131),
+    ("RIGHT", 132),
+    ("DOWN", 133),
+    ("HOME", 134),
+    ("END", 135),
+    ("PAGEUP", 136),
+    ("PAGEDOWN", 137),
+    ("INSERT", 138),
+    ("DELETE", 139),
+    ("ESC", 140),
+];
+
+impl Timeline {
+    pub fn parse(contents: &str) -> Result<Timeline> {
+        let mut events = Vec::new();
+        for (index, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (cycle, key) = line
+                .split_once(char::is_whitespace)
+                .with_context(|| format!("Line {}: expect `cycle key`", index + 1))?;
+            let cycle = cycle
+                .parse::<u64>()
+                .with_context(|| format!("Line {}: invalid cycle {cycle}", index + 1))?;
+            events.push((
+                cycle,
+                parse_key(key.trim()).with_context(|| format!("Line {}", index + 1))?,
+            ));
+        }
+        events.sort_by_key(|(cycle, _)| *cycle);
+        Ok(Timeline(events))
+    }
+
+    /// The key pressed at `cycle`
+    pub fn key_at(&self, cycle: u64) -> u16 {
+        self.0
+            .iter()
+            .take_while(|(c, _)| *c <= cycle)
+            .last()
+            .map_or(0, |(_, key)| *key)
+    }
+
+    /// The first cycle after `cycle` at which the key changes
+    pub fn next_change(&self, cycle: u64) -> Option<u64> {
+        self.0.iter().map(|(c, _)| *c).find(|c| *c > cycle)
+    }
+}
+
+fn parse_key(key: &str) -> Result<u16> {
+    if let Ok(value) = key.parse::<u16>() {
+        return Ok(value);
+    }
+    if let Some((_, value)) = KEYS.iter().find(|(name, _)| name.eq_ignore_ascii_case(key)) {
+        return Ok(*value);
+    }
+    if let Some(n) = key
+        .strip_prefix(['F', 'f'])
+        .and_then(|n| n.parse::<u16>().ok())
+        .filter(|n| (1..=12).contains(n))
+    {
+        return Ok(140 + n);
+    }
+    let mut chars = key.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_graphic() => Ok(c.to_ascii_uppercase() as u16),
+        _ => bail!("Unknown key {key}"),
+    }
+}
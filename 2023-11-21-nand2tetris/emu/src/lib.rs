@@ -0,0 +1,7 @@
+pub mod cpu;
+pub mod debugger;
+pub mod keyboard;
+pub mod loader;
+pub mod screen;
+
+pub use cpu::{Computer, Stop};
@@ -0,0 +1,112 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use asm::disassembler::SymbolFile;
+use asm::symbol_tables::SymbolKind;
+use asm::{hack, parse_source_map, Location, Program};
+
+use crate::cpu::ROM_SIZE;
+
+/// Names of ROM and RAM addresses defined by a program, and where its instructions
+/// come from
+#[derive(Default)]
+pub struct Symbols {
+    pub labels: BTreeMap<String, u16>,
+    pub variables: BTreeMap<String, u16>,
+    /// Indexed by ROM address, empty when unknown
+    pub source_map: Vec<Location>,
+}
+
+/// Read the instruction words of a `.hack` file, or assemble an `.asm` file in-process
+pub fn load(path: &Path) -> Result<Vec<u16>> {
+    let contents = read(path)?;
+
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("hack") => fit(path, hack::parse(&contents)?),
+        Some("asm") => Ok(assemble(path, &contents)?.words),
+        _ => bail!("Input file must be .hack or .asm file."),
+    }
+}
+
+/// Like `load`, also returning the program's symbols. They come from the assembler for
+/// an `.asm` file, and from the `.sym` and `.map` files next to a `.hack` file if there
+/// are some.
+pub fn load_with_symbols(path: &Path) -> Result<(Vec<u16>, Symbols)> {
+    let contents = read(path)?;
+
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("hack") => {
+            let words = fit(path, hack::parse(&contents)?)?;
+            let mut symbols = Symbols::default();
+
+            let sym_path = path.with_extension("sym");
+            if sym_path.exists() {
+                let file = SymbolFile::parse(&read(&sym_path)?)
+                    .with_context(|| format!("Malformed symbol file {}", sym_path.display()))?;
+                let invert = |names: BTreeMap<u16, Vec<String>>| {
+                    names
+                        .into_iter()
+                        .flat_map(|(address, names)| names.into_iter().map(move |n| (n, address)))
+                        .collect()
+                };
+                symbols.labels = invert(file.labels);
+                symbols.variables = invert(file.variables);
+            }
+
+            let map_path = path.with_extension("map");
+            if map_path.exists() {
+                symbols.source_map = parse_source_map(&read(&map_path)?)
+                    .with_context(|| format!("Malformed source map {}", map_path.display()))?;
+            }
+
+            Ok((words, symbols))
+        }
+        Some("asm") => {
+            let mut program = assemble(path, &contents)?;
+            for location in &mut program.source_map {
+                if location.file.is_empty() {
+                    location.file = path.display().to_string();
+                }
+            }
+            let mut symbols = Symbols {
+                source_map: program.source_map,
+                ..Default::default()
+            };
+            for (name, symbol) in program.symbols {
+                match symbol.kind {
+                    SymbolKind::Label => symbols.labels.insert(name, symbol.address),
+                    _ => symbols.variables.insert(name, symbol.address),
+                };
+            }
+            Ok((program.words, symbols))
+        }
+        _ => bail!("Input file must be .hack or .asm file."),
+    }
+}
+
+// `Computer::new` only takes programs that fit in ROM
+fn fit(path: &Path, words: Vec<u16>) -> Result<Vec<u16>> {
+    if words.len() > ROM_SIZE {
+        bail!(
+            "{} has {} instructions, but ROM only holds {ROM_SIZE}",
+            path.display(),
+            words.len()
+        );
+    }
+    Ok(words)
+}
+
+fn read(path: &Path) -> Result<String> {
+    fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))
+}
+
+fn assemble(path: &Path, contents: &str) -> Result<Program> {
+    let options = asm::Options {
+        include_dir: path.parent().map(Path::to_path_buf),
+        ..Default::default()
+    };
+    Ok(asm::assemble_with(contents, &options)
+        .map_err(|d| d.in_file(&path.display().to_string()))?)
+}
@@ -0,0 +1,170 @@
+use std::collections::BTreeSet;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+
+use emu::keyboard::Timeline;
+use emu::loader::load;
+use emu::{screen, Computer, Stop};
+
+const USAGE: &str = "Usage: emu [-n cycles] [--set addr=value]... [--ram addr[..end]]... \
+[--kbd timeline] [--screen out.pbm|out.png [--dump-at cycle]...] file";
+
+fn main() -> Result<()> {
+    let arguments: Vec<String> = env::args().skip(1).collect();
+
+    let mut max_cycles: u64 = 1_000_000;
+    let mut presets: Vec<(usize, u16)> = Vec::new();
+    let mut watched: Vec<(usize, usize)> = Vec::new();
+    let mut timeline = Timeline::parse("")?;
+    let mut screen_path: Option<PathBuf> = None;
+    let mut dumps: BTreeSet<u64> = BTreeSet::new();
+    let mut file = None;
+
+    let mut args = arguments.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-n" => {
+                let n = args.next().context(USAGE)?;
+                max_cycles = n
+                    .parse()
+                    .with_context(|| format!("Invalid number of cycles {n}"))?;
+            }
+            "--set" => {
+                let s = args.next().context(USAGE)?;
+                let (address, value) = s
+                    .split_once('=')
+                    .with_context(|| format!("Expect addr=value. Got {s} instead"))?;
+                presets.push((parse_address(address)?, parse_value(value)?));
+            }
+            "--ram" => {
+                let s = args.next().context(USAGE)?;
+                watched.push(match s.split_once("..") {
+                    Some((start, end)) => (parse_address(start)?, parse_address(end)?),
+                    None => {
+                        let address = parse_address(s)?;
+                        (address, address + 1)
+                    }
+                });
+            }
+            "--kbd" => {
+                let path = args.next().context(USAGE)?;
+                let contents =
+                    fs::read_to_string(path).with_context(|| format!("Failed to read {path}"))?;
+                timeline = Timeline::parse(&contents)?;
+            }
+            "--screen" => screen_path = Some(args.next().context(USAGE)?.into()),
+            "--dump-at" => {
+                let n = args.next().context(USAGE)?;
+                dumps.insert(n.parse().with_context(|| format!("Invalid cycle {n}"))?);
+            }
+            _ if file.is_none() => file = Some(arg),
+            _ => bail!(USAGE),
+        }
+    }
+    let Some(file) = file else {
+        bail!(USAGE);
+    };
+    if !dumps.is_empty() && screen_path.is_none() {
+        bail!("--dump-at needs --screen");
+    }
+
+    let mut computer = Computer::new(&load(Path::new(file))?);
+    for (address, value) in presets {
+        computer.ram_mut()[address] = value;
+    }
+
+    // Run in slices so that the keyboard changes and dumps happen at exact cycles
+    let stop = loop {
+        let now = computer.cycles();
+        computer.set_keyboard(timeline.key_at(now));
+        if dumps.remove(&now) {
+            let path = screen_path.as_ref().unwrap();
+            dump_screen(&computer, &suffixed(path, now))?;
+        }
+
+        let until = [
+            timeline.next_change(now),
+            dumps.range(now + 1..).next().copied(),
+            Some(max_cycles),
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+        .unwrap();
+        let stop = computer.run(until.saturating_sub(now));
+        if stop == Stop::Halted || computer.cycles() >= max_cycles {
+            break stop;
+        }
+    };
+    if let Some(path) = &screen_path {
+        dump_screen(&computer, path)?;
+        // The last slice ends on the cycle of the stop. A halted program never changes the
+        // screen again, so later cycles get the same image.
+        let end = computer.cycles();
+        for &cycle in dumps.range(end..) {
+            if cycle == end || stop == Stop::Halted {
+                dump_screen(&computer, &suffixed(path, cycle))?;
+            } else {
+                eprintln!("warning: no dump at cycle {cycle}, the run stopped at {end}");
+            }
+        }
+    }
+
+    match stop {
+        Stop::Halted => println!("Halted after {} cycles", computer.cycles()),
+        Stop::CycleLimit => println!("Stopped after {} cycles", computer.cycles()),
+    }
+    println!(
+        "A = {}, D = {}, PC = {}",
+        computer.a() as i16,
+        computer.d() as i16,
+        computer.pc()
+    );
+    for (start, end) in watched {
+        for address in start..end {
+            println!("RAM[{address}] = {}", computer.ram()[address] as i16);
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_address(s: &str) -> Result<usize> {
+    let address = s
+        .parse::<usize>()
+        .with_context(|| format!("Invalid RAM address {s}"))?;
+    if address >= emu::cpu::RAM_SIZE {
+        bail!("RAM address {address} is out of range");
+    }
+    Ok(address)
+}
+
+fn parse_value(s: &str) -> Result<u16> {
+    s.parse::<i16>()
+        .map(|v| v as u16)
+        .or_else(|_| s.parse::<u16>())
+        .with_context(|| format!("Invalid 16-bit value {s}"))
+}
+
+fn dump_screen(computer: &Computer, path: &Path) -> Result<()> {
+    let image = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("pbm") => screen::pbm(computer.ram()),
+        Some("png") => screen::png(computer.ram()),
+        _ => bail!("Screen dumps must be .pbm or .png files"),
+    };
+    fs::write(path, image).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+// `out.png` dumped at cycle 100 becomes `out-100.png`
+fn suffixed(path: &Path, cycle: u64) -> PathBuf {
+    let mut name = path.file_stem().unwrap_or_default().to_owned();
+    name.push(format!("-{cycle}"));
+    if let Some(ext) = path.extension() {
+        name.push(".");
+        name.push(ext);
+    }
+    path.with_file_name(name)
+}
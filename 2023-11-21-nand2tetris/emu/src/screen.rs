@@ -0,0 +1,100 @@
+use crate::cpu::SCREEN;
+
+pub const WIDTH: usize = 512;
+pub const HEIGHT: usize = 256;
+
+/// Whether the pixel at (`row`, `col`) is black. The leftmost pixel of a word is its least
+/// significant bit.
+pub fn pixel(ram: &[u16], row: usize, col: usize) -> bool {
+    ram[SCREEN + row * WIDTH / 16 + col / 16] & (1 << (col % 16)) != 0
+}
+
+// One row packed 8 pixels per byte, leftmost pixel in the most significant bit
+fn packed_row(ram: &[u16], row: usize, black: bool) -> impl Iterator<Item = u8> + '_ {
+    (0..WIDTH / 8).map(move |byte| {
+        (0..8).fold(0u8, |acc, bit| {
+            let on = pixel(ram, row, byte * 8 + bit) == black;
+            acc << 1 | u8::from(on)
+        })
+    })
+}
+
+/// Encode the screen as a binary PBM (P4) image
+pub fn pbm(ram: &[u16]) -> Vec<u8> {
+    let mut out = format!("P4\n{WIDTH} {HEIGHT}\n").into_bytes();
+    for row in 0..HEIGHT {
+        out.extend(packed_row(ram, row, true));
+    }
+    out
+}
+
+/// Encode the screen as a 1-bit grayscale PNG image
+pub fn png(ram: &[u16]) -> Vec<u8> {
+    // Each scanline starts with filter type 0 (none); in grayscale 1 is white
+    let mut raw = Vec::with_capacity(HEIGHT * (WIDTH / 8 + 1));
+    for row in 0..HEIGHT {
+        raw.push(0);
+        raw.extend(packed_row(ram, row, false));
+    }
+
+    let mut ihdr = Vec::new();
+    ihdr.extend((WIDTH as u32).to_be_bytes());
+    ihdr.extend((HEIGHT as u32).to_be_bytes());
+    // bit depth 1, grayscale, deflate, no filter, no interlace
+    ihdr.extend([1, 0, 0, 0, 0]);
+
+    let mut out = b"\x89PNG\r\n\x1a\n".to_vec();
+    chunk(&mut out, b"IHDR", &ihdr);
+    chunk(&mut out, b"IDAT", &zlib_stored(&raw));
+    chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+fn chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend((data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend(kind);
+    out.extend(data);
+    let crc = crc32(&out[start..]);
+    out.extend(crc.to_be_bytes());
+}
+
+// A zlib stream made of uncompressed deflate blocks. The screen is only 16K, so
+// compression is not worth a dependency.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let blocks: Vec<&[u8]> = data.chunks(0xFFFF).collect();
+    for (i, block) in blocks.iter().enumerate() {
+        out.push(u8::from(i + 1 == blocks.len()));
+        let len = block.len() as u16;
+        out.extend(len.to_le_bytes());
+        out.extend((!len).to_le_bytes());
+        out.extend(*block);
+    }
+    out.extend(adler32(data).to_be_bytes());
+    out
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    b << 16 | a
+}
@@ -0,0 +1,38 @@
+use emu::cpu::{Computer, Stop};
+
+fn computer(source: &str) -> Computer {
+    Computer::new(&asm::assemble(source).unwrap().words)
+}
+
+#[test]
+fn jumps_to_themselves_halt() {
+    let mut end = computer("@1\nD=A\n(END)\n@END\n0;JMP\n");
+    assert_eq!(end.run(100), Stop::Halted);
+    assert_eq!(end.cycles(), 2);
+
+    let mut jump = computer("@2\nA=A\n0;JMP\n");
+    assert_eq!(jump.run(100), Stop::Halted);
+    assert_eq!(jump.pc(), 2);
+}
+
+#[test]
+fn loops_that_write_d_do_not_halt() {
+    let mut load = computer("@0\nD=D+1;JMP\n");
+    assert_eq!(load.run(100), Stop::CycleLimit);
+    assert_eq!(load.d(), 50);
+
+    let mut jump = computer("@2\nA=A\nD=D+1;JMP\n");
+    assert_eq!(jump.run(100), Stop::CycleLimit);
+    assert_eq!(jump.d(), 98);
+}
+
+#[test]
+fn loops_that_write_memory_do_not_halt() {
+    let mut load = computer("@0\nM=M+1;JMP\n");
+    assert_eq!(load.run(100), Stop::CycleLimit);
+    assert_eq!(load.ram()[0], 50);
+
+    let mut jump = computer("@2\nA=A\nM=M-1;JMP\n");
+    assert_eq!(jump.run(100), Stop::CycleLimit);
+    assert_eq!(jump.ram()[2] as i16, -98);
+}
@@ -0,0 +1,194 @@
+use std::fs;
+use std::path::PathBuf;
+
+use emu::debugger::{Debugger, Event};
+use emu::loader::load_with_symbols;
+
+// Counts `x` down from 3, then calls nothing and halts
+const PROGRAM: &str = "\
+(Main.main)
+@3
+D=A
+@x
+M=D
+(Main.main$LOOP)
+@x
+MD=M-1
+@Main.main$LOOP
+D;JGT
+(Main.end)
+@Main.end
+0;JMP
+";
+
+fn debugger(name: &str, source: &str) -> Debugger {
+    let path: PathBuf =
+        std::env::temp_dir().join(format!("emu-debugger-{}-{name}.asm", std::process::id()));
+    fs::write(&path, source).unwrap();
+    let (words, symbols) = load_with_symbols(&path).unwrap();
+    fs::remove_file(path).unwrap();
+    Debugger::new(&words, symbols)
+}
+
+#[test]
+fn addresses_are_numbers_or_symbols() {
+    let debugger = debugger("addresses", PROGRAM);
+    assert_eq!(debugger.rom_address("Main.main$LOOP"), Some(4));
+    assert_eq!(debugger.rom_address("7"), Some(7));
+    assert_eq!(debugger.rom_address("32768"), None);
+    assert_eq!(debugger.rom_address("missing"), None);
+
+    assert_eq!(debugger.ram_address("x"), Some(16));
+    assert_eq!(debugger.ram_address("LCL"), Some(1));
+    assert_eq!(debugger.ram_address("R13"), Some(13));
+    assert_eq!(debugger.ram_address("24576"), Some(24576));
+    assert_eq!(debugger.ram_address("32768"), None);
+    assert_eq!(debugger.ram_address("missing"), None);
+}
+
+#[test]
+fn breakpoints_stop_before_their_instruction() {
+    let mut debugger = debugger("breakpoints", PROGRAM);
+    debugger.breakpoints.insert(4);
+    assert!(matches!(debugger.run(100), Event::Breakpoint(4)));
+    assert_eq!(debugger.computer.ram()[16], 3);
+
+    // Resuming from a breakpoint executes it
+    assert!(matches!(debugger.run(100), Event::Breakpoint(4)));
+    assert_eq!(debugger.computer.ram()[16], 2);
+
+    debugger.breakpoints.clear();
+    assert!(matches!(debugger.run(100), Event::Halted));
+    assert_eq!(debugger.computer.pc(), 8);
+}
+
+#[test]
+fn watches_stop_on_changes_only() {
+    let mut debugger = debugger("watches", PROGRAM);
+    debugger.watch(16);
+    debugger.watch(17);
+    let mut changes = Vec::new();
+    loop {
+        match debugger.run(100) {
+            Event::Watch { address, old, new } => changes.push((address, old, new)),
+            Event::Halted => break,
+            _ => panic!("unexpected event"),
+        }
+    }
+    assert_eq!(changes, [(16, 0, 3), (16, 3, 2), (16, 2, 1), (16, 1, 0)]);
+}
+
+#[test]
+fn step_limits_are_done() {
+    let mut debugger = debugger("steps", PROGRAM);
+    assert!(matches!(debugger.run(3), Event::Done));
+    assert_eq!(debugger.computer.pc(), 3);
+}
+
+#[test]
+fn functions_skip_local_labels() {
+    let debugger = debugger("functions", PROGRAM);
+    assert_eq!(debugger.function_at(0), Some("Main.main"));
+    assert_eq!(debugger.function_at(6), Some("Main.main"));
+    assert_eq!(debugger.function_at(9), Some("Main.end"));
+}
+
+#[test]
+fn locations_come_from_the_source_map() {
+    let debugger = debugger("locations", PROGRAM);
+    let location = debugger.location(5).unwrap();
+    assert!(location.ends_with("-locations.asm:8"), "{location}");
+    assert_eq!(debugger.location(10), None);
+}
+
+#[test]
+fn disassembly_marks_labels_breakpoints_and_pc() {
+    let mut debugger = debugger("disassembly", PROGRAM);
+    debugger.breakpoints.insert(6);
+    debugger.run(4);
+    let expected = [
+        "   00003   M=D",
+        "           (Main.main$LOOP)",
+        "=> 00004   @16  // x",
+        "   00005   MD=M-1",
+        "  *00006   @4  // Main.main$LOOP",
+    ];
+    assert_eq!(debugger.disassemble(1, 2), expected.join("\n") + "\n");
+}
+
+#[test]
+fn stacks_follow_the_saved_frames() {
+    let source = "\
+(Sys.init)
+@Main.f
+0;JMP
+(Sys.init$ret.0)
+@Sys.init$ret.0
+0;JMP
+(Main.f)
+D=0
+@Main.f
+0;JMP
+";
+    let mut debugger = debugger("stack", source);
+    // Sys.init, called by the bootstrap code, pushed 7 and called Main.f, which pushed 9
+    let ram = debugger.computer.ram_mut();
+    ram[0] = 268;
+    ram[1] = 267;
+    ram[2] = 261;
+    ram[261] = 7;
+    ram[262..267].copy_from_slice(&[2, 261, 256, 0, 0]);
+    ram[267] = 9;
+    debugger.computer.set_pc(4);
+
+    let frames = debugger.stack();
+    let summary: Vec<_> = frames
+        .iter()
+        .map(|f| (f.function.as_str(), f.pc, f.args.clone(), f.stack.clone()))
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            ("Main.f", 4, vec![7], vec![9]),
+            ("Sys.init", 2, vec![], vec![])
+        ]
+    );
+
+    // Not a VM stack
+    debugger.computer.ram_mut()[2] = 0;
+    assert!(debugger.stack().is_empty());
+}
+
+#[test]
+fn edb_runs_commands_from_stdin() {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    let path = std::env::temp_dir().join(format!("emu-edb-{}.asm", std::process::id()));
+    fs::write(&path, PROGRAM).unwrap();
+    let mut edb = Command::new(env!("CARGO_BIN_EXE_edb"))
+        .arg(&path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // The empty line repeats `continue`
+    let commands = "break Main.main$LOOP\ncontinue\n\nprint x\nwatch x\ncontinue\nquit\n";
+    edb.stdin
+        .take()
+        .unwrap()
+        .write_all(commands.as_bytes())
+        .unwrap();
+    let output = edb.wait_with_output().unwrap();
+    fs::remove_file(path).unwrap();
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "{stdout}");
+    assert_eq!(
+        stdout.matches("=>*00004   @16  // x").count(),
+        2,
+        "{stdout}"
+    );
+    assert!(stdout.contains("RAM[16] = 2"), "{stdout}");
+    assert!(stdout.contains("RAM[16] changed from 2 to 1"), "{stdout}");
+}
@@ -0,0 +1,27 @@
+use std::fs;
+use std::path::PathBuf;
+
+use emu::cpu::ROM_SIZE;
+use emu::loader::{load, load_with_symbols};
+
+fn hack_file(name: &str, words: usize) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("emu-{}-{name}.hack", std::process::id()));
+    fs::write(&path, "0000000000000000\n".repeat(words)).unwrap();
+    path
+}
+
+#[test]
+fn programs_larger_than_rom_are_an_error() {
+    let path = hack_file("large", ROM_SIZE + 1);
+    let error = load(&path).unwrap_err().to_string();
+    assert!(error.contains("ROM only holds 32768"), "{error}");
+    assert!(load_with_symbols(&path).is_err());
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn programs_that_fill_rom_load() {
+    let path = hack_file("full", ROM_SIZE);
+    assert_eq!(load(&path).unwrap().len(), ROM_SIZE);
+    fs::remove_file(path).unwrap();
+}
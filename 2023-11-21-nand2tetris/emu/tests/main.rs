@@ -0,0 +1,67 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+// A directory of its own for each test, with `program` as `Prog.asm`
+fn setup(name: &str, program: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("emu-main-{}-{name}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("Prog.asm"), program).unwrap();
+    dir
+}
+
+fn emu(dir: &Path, args: &[&str]) -> Output {
+    let output = Command::new(env!("CARGO_BIN_EXE_emu"))
+        .args(args)
+        .arg(dir.join("Prog.asm"))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    output
+}
+
+#[test]
+fn dumps_at_the_cycle_limit_are_written() {
+    let dir = setup("limit", "(LOOP)\n@SCREEN\nM=M+1\n@LOOP\n0;JMP\n");
+    let screen = dir.join("s.pbm");
+    let screen = screen.to_str().unwrap();
+    let output = emu(
+        &dir,
+        &[
+            "-n",
+            "10",
+            "--screen",
+            screen,
+            "--dump-at",
+            "10",
+            "--dump-at",
+            "20",
+        ],
+    );
+
+    assert!(dir.join("s-10.pbm").exists());
+    assert!(!dir.join("s-20.pbm").exists());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("no dump at cycle 20, the run stopped at 10"),
+        "{stderr}"
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn dumps_after_a_halt_show_the_final_screen() {
+    let dir = setup("halt", "@SCREEN\nM=-1\n(END)\n@END\n0;JMP\n");
+    let screen = dir.join("s.pbm");
+    let screen = screen.to_str().unwrap();
+    emu(
+        &dir,
+        &["--screen", screen, "--dump-at", "0", "--dump-at", "100"],
+    );
+
+    let before = fs::read(dir.join("s-0.pbm")).unwrap();
+    let after = fs::read(dir.join("s-100.pbm")).unwrap();
+    assert_ne!(before, after);
+    assert_eq!(after, fs::read(dir.join("s.pbm")).unwrap());
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,87 @@
+use asm::{assemble_with, Options};
+use emu::cpu::{Computer, Stop};
+
+// Multiplies R0 by R1 into R2 and sums 1..=R1 into R3, in the style of generated code: full of
+// reloads, round trips, jumps to the next instruction and dead code after jumps
+const PROGRAM: &str = "
+@6
+D=A
+@R0
+M=D
+@7
+D=A
+@R1
+M=D
+@R2
+M=0
+@R1
+D=M
+@R4
+M=D
+(MULTIPLY)
+@R4
+D=M
+@SUM
+D;JEQ
+@R0
+D=M
+@R2
+M=D+M
+@R2
+D=M
+M=D
+@R4
+M=M-1
+@MULTIPLY
+0;JMP
+@R2
+M=0
+(SUM)
+@R3
+M=0
+@R1
+D=M
+@R4
+M=D
+@R4
+D=M
+(LOOP)
+@NEXT
+0;JMP
+(NEXT)
+@R4
+D=M
+@END
+D;JEQ
+@R3
+M=D+M
+@R4
+M=M-1
+@LOOP
+0;JMP
+D=0
+(END)
+@END
+0;JMP
+";
+
+fn run(optimize: bool) -> (usize, Computer) {
+    let options = Options {
+        optimize,
+        ..Default::default()
+    };
+    let words = assemble_with(PROGRAM, &options).unwrap().words;
+    let mut computer = Computer::new(&words);
+    assert_eq!(computer.run(10_000), Stop::Halted);
+    (words.len(), computer)
+}
+
+#[test]
+fn optimized_programs_compute_the_same_ram() {
+    let (plain_size, plain) = run(false);
+    let (optimized_size, optimized) = run(true);
+    assert_eq!(&plain.ram()[..5], [6, 7, 42, 28, 0]);
+    assert_eq!(optimized.ram(), plain.ram());
+    assert!(optimized_size < plain_size);
+    assert!(optimized.cycles() < plain.cycles());
+}
@@ -0,0 +1,33 @@
+use std::env;
+use std::fs;
+
+use anyhow::{bail, Context, Result};
+
+use asm::hack;
+
+mod recompiler;
+
+// Usage: h2c file.hack
+//
+// Translate a `.hack` file into a C program, written to stdout. Compiled with
+// e.g. `cc -O2 -o prog prog.c`, it runs the program natively and takes the same
+// options as `emu`.
+fn main() -> Result<()> {
+    let arguments: Vec<String> = env::args().collect();
+    let [_, file] = arguments.as_slice() else {
+        bail!(
+            "Usage: {} file.hack",
+            arguments.first().unwrap_or(&"h2c".to_string())
+        );
+    };
+
+    let contents = fs::read_to_string(file).with_context(|| format!("Failed to read {file}"))?;
+    let words = hack::parse(&contents)?;
+    if words.len() > 32768 {
+        bail!("Program does not fit in ROM");
+    }
+
+    print!("{}", recompiler::translate(&words));
+
+    Ok(())
+}
@@ -0,0 +1,266 @@
+use std::fmt::Write;
+
+use asm::disassembler::{comp_mnemonic, decode};
+
+const ROM_SIZE: usize = 32768;
+
+const PRELUDE: &str = r#"#include <errno.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#define SCREEN 16384
+#define KBD 24576
+#define MAX_WATCHED 64
+
+static uint16_t ram[32768];
+static uint64_t cycles;
+
+/* Define HACK_HOOKS as the name of a header to replace these hooks, e.g.
+ * cc -DHACK_HOOKS='"hooks.h"' */
+#ifdef HACK_HOOKS
+#include HACK_HOOKS
+#endif
+
+/* Value read from the keyboard register */
+#ifndef HACK_KEYBOARD
+#define HACK_KEYBOARD(cycles) ram[KBD]
+#endif
+
+/* Called after every write to the screen memory map */
+#ifndef HACK_SCREEN
+#define HACK_SCREEN(address, value)
+#endif
+
+static inline uint16_t rd(uint16_t address) {
+    return address == KBD ? HACK_KEYBOARD(cycles) : ram[address];
+}
+
+static inline void wr(uint16_t address, uint16_t value) {
+    ram[address] = value;
+    if (address >= SCREEN && address < KBD) {
+        HACK_SCREEN(address, value);
+    }
+}
+
+/* The Hack ALU, for control bits that have no mnemonic */
+static inline uint16_t alu(uint16_t x, uint16_t y, unsigned c) {
+    if (c & 040) x = 0;
+    if (c & 020) x = ~x;
+    if (c & 010) y = 0;
+    if (c & 004) y = ~y;
+    uint16_t out = (c & 002) ? (uint16_t)(x + y) : (uint16_t)(x & y);
+    return (c & 001) ? (uint16_t)~out : out;
+}
+
+static long parse(const char *s, long max) {
+    char *end;
+    long value = strtol(s, &end, 10);
+    if (*s == '\0' || *end != '\0' || value < 0 || value > max) {
+        fprintf(stderr, "Invalid number %s\n", s);
+        exit(1);
+    }
+    return value;
+}
+
+static void write_pbm(const char *path) {
+    FILE *f = fopen(path, "wb");
+    if (!f) {
+        fprintf(stderr, "Failed to write %s\n", path);
+        exit(1);
+    }
+    fprintf(f, "P4\n512 256\n");
+    for (int row = 0; row < 256; row++) {
+        for (int byte = 0; byte < 64; byte++) {
+            uint16_t word = ram[SCREEN + row * 32 + byte / 2];
+            unsigned bits = (byte % 2) ? word >> 8 : word & 0xFF;
+            unsigned packed = 0;
+            for (int bit = 0; bit < 8; bit++) {
+                packed |= ((bits >> bit) & 1) << (7 - bit);
+            }
+            fputc(packed, f);
+        }
+    }
+    fclose(f);
+}
+
+/* A halt check comes before the tick, as in the emulator */
+#define TICK(i) if (cycles == max) { pc = (i); goto limit; } cycles++;
+#define HALT(i) { pc = (i); goto halted; }
+
+int main(int argc, char **argv) {
+    uint64_t max = 1000000;
+    long watched[MAX_WATCHED][2];
+    int nwatched = 0;
+    const char *screen = NULL;
+
+    for (int i = 1; i < argc; i++) {
+        if (!strcmp(argv[i], "-n") && i + 1 < argc) {
+            char *s = argv[++i], *end;
+            errno = 0;
+            max = strtoull(s, &end, 10);
+            /* strtoull takes leading spaces and negates a `-` */
+            if (*s < '0' || *s > '9' || *end != '\0' || errno == ERANGE) {
+                fprintf(stderr, "Invalid number of cycles %s\n", s);
+                return 1;
+            }
+        } else if (!strcmp(argv[i], "--set") && i + 1 < argc) {
+            char *s = argv[++i], *eq = strchr(s, '=');
+            if (!eq) {
+                fprintf(stderr, "Expect addr=value. Got %s instead\n", s);
+                return 1;
+            }
+            *eq = '\0';
+            char *end;
+            long value = strtol(eq + 1, &end, 10);
+            /* Signed or unsigned, as in emu */
+            if (eq[1] == '\0' || *end != '\0' || value < -32768 || value > 65535) {
+                fprintf(stderr, "Invalid 16-bit value %s\n", eq + 1);
+                return 1;
+            }
+            ram[parse(s, 32767)] = (uint16_t)value;
+        } else if (!strcmp(argv[i], "--ram") && i + 1 < argc) {
+            if (nwatched == MAX_WATCHED) {
+                fprintf(stderr, "At most %d --ram options are supported\n", MAX_WATCHED);
+                return 1;
+            }
+            char *s = argv[++i], *dots = strstr(s, "..");
+            if (dots) {
+                *dots = '\0';
+                watched[nwatched][0] = parse(s, 32767);
+                watched[nwatched][1] = parse(dots + 2, 32767);
+            } else {
+                watched[nwatched][0] = parse(s, 32767);
+                watched[nwatched][1] = watched[nwatched][0] + 1;
+            }
+            nwatched++;
+        } else if (!strcmp(argv[i], "--screen") && i + 1 < argc) {
+            screen = argv[++i];
+        } else {
+            fprintf(stderr, "Usage: %s [-n cycles] [--set addr=value]... [--ram addr[..end]]... [--screen out.pbm]\n", argv[0]);
+            return 1;
+        }
+    }
+
+    uint16_t a = 0, d = 0, pc = 0, t, out;
+    int halt;
+
+dispatch:
+    switch (pc) {
+"#;
+
+const EPILOGUE: &str = r#"    }
+
+halted:
+    halt = 1;
+    goto done;
+limit:
+    halt = 0;
+done:
+    if (screen) {
+        write_pbm(screen);
+    }
+    printf("%s after %llu cycles\n", halt ? "Halted" : "Stopped", (unsigned long long)cycles);
+    printf("A = %d, D = %d, PC = %u\n", (int16_t)a, (int16_t)d, pc);
+    for (int i = 0; i < nwatched; i++) {
+        for (long address = watched[i][0]; address < watched[i][1]; address++) {
+            printf("RAM[%ld] = %d\n", address, (int16_t)ram[address]);
+        }
+    }
+    return 0;
+}
+"#;
+
+/// Translate a program into a C program with one `case` per ROM address. Instructions
+/// fall through to the next case and jumps go back to the `switch`. The program takes
+/// the `-n`, `--set`, `--ram` and `--screen` (PBM only) options of `emu` and prints the
+/// same report, so that both can be compared.
+pub fn translate(words: &[u16]) -> String {
+    assert!(words.len() <= ROM_SIZE, "Program does not fit in ROM");
+    let word_at = |address: usize| words.get(address % ROM_SIZE).copied().unwrap_or(0);
+
+    let mut out = String::from(PRELUDE);
+    for (address, &word) in words.iter().enumerate() {
+        let mnemonic = decode(word).unwrap_or_else(|| format!("{word:016b}"));
+        writeln!(out, "    case {address}: /* {mnemonic} */").unwrap();
+
+        if word & 0x8000 == 0 {
+            let next = word_at(address + 1);
+            // Unless the jump writes D or M, e.g. `@5 M=M+1;JMP`
+            if word as usize == address
+                && next & 0x8000 != 0
+                && next & 0b111 == 0b111
+                && next & 0b011_000 == 0
+            {
+                writeln!(out, "        HALT({address});").unwrap();
+            }
+            writeln!(out, "        TICK({address}); a = {word};").unwrap();
+            continue;
+        }
+
+        let dest = (word >> 3) & 0b111;
+        let jump = word & 0b111;
+        if jump == 0b111 && dest == 0 {
+            // A jump to itself that writes no register never ends
+            writeln!(out, "        if (a == {address}) HALT({address});").unwrap();
+        }
+        writeln!(out, "        TICK({address});").unwrap();
+        writeln!(out, "        t = a; out = {};", comp(word >> 6)).unwrap();
+        // M and the jump use the old value of A
+        if dest & 0b001 != 0 {
+            writeln!(out, "        wr(t & 0x7FFF, out);").unwrap();
+        }
+        if dest & 0b010 != 0 {
+            writeln!(out, "        d = out;").unwrap();
+        }
+        if dest & 0b100 != 0 {
+            writeln!(out, "        a = out;").unwrap();
+        }
+        let condition = match jump {
+            0b000 => continue,
+            0b001 => "(int16_t)out > 0",
+            0b010 => "out == 0",
+            0b011 => "(int16_t)out >= 0",
+            0b100 => "(int16_t)out < 0",
+            0b101 => "out != 0",
+            0b110 => "(int16_t)out <= 0",
+            _ => "1",
+        };
+        writeln!(
+            out,
+            "        if ({condition}) {{ pc = t & 0x7FFF; goto dispatch; }}"
+        )
+        .unwrap();
+    }
+
+    // Past the program the ROM holds zeros, i.e. `@0`
+    writeln!(out, "        pc = {};", words.len() % ROM_SIZE).unwrap();
+    writeln!(out, "    default:").unwrap();
+    writeln!(
+        out,
+        "        TICK(pc); a = 0; pc = (pc + 1) & 0x7FFF; goto dispatch;"
+    )
+    .unwrap();
+    out.push_str(EPILOGUE);
+    out
+}
+
+// C expression of a computation, reading the registers `a` and `d`
+fn comp(c: u16) -> String {
+    let y = if c & 0b100_0000 != 0 {
+        "rd(a & 0x7FFF)"
+    } else {
+        "a"
+    };
+    match comp_mnemonic(c) {
+        Some(mnemonic) => {
+            let expression = mnemonic
+                .replace('!', "~")
+                .replace('D', "d")
+                .replace(['A', 'M'], y);
+            format!("(uint16_t)({expression})")
+        }
+        None => format!("alu(d, {y}, 0{:o})", c & 0b11_1111),
+    }
+}
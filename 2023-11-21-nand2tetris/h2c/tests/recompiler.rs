@@ -0,0 +1,158 @@
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use emu::cpu::{Computer, Stop};
+use hvm::generator::HackGenerator;
+
+// A directory of its own for each test
+fn setup(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("h2c-{}-{name}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+// hvm/test/Compare translated to assembly, as `hvm` does
+fn compare_program() -> String {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../hvm/test/Compare");
+    let mut out = String::new();
+    writeln!(out, "{}", HackGenerator::bootstrap()).unwrap();
+    let mut generator = HackGenerator::new();
+    for (filename, instructions) in hvm::loader::load(&dir).unwrap() {
+        generator.set_filename(filename);
+        for instruction in instructions {
+            writeln!(
+                out,
+                "{}",
+                generator.generate(instruction).unwrap().join("\n")
+            )
+            .unwrap();
+        }
+    }
+    out
+}
+
+// Recompile `source` with h2c and cc into `dir/prog`
+fn build(dir: &Path, source: &str) -> PathBuf {
+    let words = asm::assemble(source).unwrap().words;
+    let hack: String = words.iter().map(|w| format!("{w:016b}\n")).collect();
+    fs::write(dir.join("prog.hack"), hack).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_h2c"))
+        .arg(dir.join("prog.hack"))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    fs::write(dir.join("prog.c"), output.stdout).unwrap();
+
+    let status = Command::new("cc")
+        .arg("-O0")
+        .arg("-o")
+        .arg(dir.join("prog"))
+        .arg(dir.join("prog.c"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+    dir.join("prog")
+}
+
+// Standard output, standard error and whether the program exited successfully
+fn run(program: &Path, args: &[&str]) -> (String, String, bool) {
+    let output = Command::new(program).args(args).output().unwrap();
+    (
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+        output.status.success(),
+    )
+}
+
+#[test]
+fn only_jumps_that_write_no_register_halt() {
+    let dir = setup("halt");
+    for (source, expected) in [
+        ("@1\nD=A\n(END)\n@END\n0;JMP\n", "Halted after 2 cycles"),
+        ("@2\nA=A\n0;JMP\n", "Halted after 2 cycles"),
+        ("@0\nD=D+1;JMP\n", "Stopped after 100 cycles"),
+        ("@2\nA=A\nD=D+1;JMP\n", "Stopped after 100 cycles"),
+        ("@0\nM=M+1;JMP\n", "Stopped after 100 cycles"),
+    ] {
+        let program = build(&dir, source);
+        let (stdout, _, success) = run(&program, &["-n", "100"]);
+        assert!(success);
+        assert!(stdout.starts_with(expected), "{source:?}: {stdout}");
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn recompiled_programs_match_emu() {
+    let source = compare_program();
+    let mut computer = Computer::new(&asm::assemble(&source).unwrap().words);
+    assert_eq!(computer.run(1_000_000), Stop::Halted);
+    assert_eq!(computer.cycles(), 7217);
+
+    let dir = setup("compare");
+    let program = build(&dir, &source);
+    let (stdout, _, success) = run(&program, &["--ram", "0..32767"]);
+    assert!(success);
+    let mut lines = stdout.lines();
+    assert_eq!(lines.next(), Some("Halted after 7217 cycles"));
+    assert_eq!(
+        lines.next().unwrap(),
+        format!(
+            "A = {}, D = {}, PC = {}",
+            computer.a() as i16,
+            computer.d() as i16,
+            computer.pc()
+        )
+    );
+    for (address, line) in lines.enumerate() {
+        let value = computer.ram()[address] as i16;
+        assert_eq!(line, format!("RAM[{address}] = {value}"));
+    }
+    assert_eq!(computer.ram()[3001], 0b1000000);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn invalid_arguments_are_reported() {
+    let dir = setup("arguments");
+    let program = build(&dir, "@1\nD=A\n(END)\n@END\n0;JMP\n");
+    for (args, error) in [
+        (&["-n", "12x"][..], "Invalid number of cycles 12x"),
+        (&["-n", "-1"], "Invalid number of cycles -1"),
+        (&["-n", ""], "Invalid number of cycles"),
+        (
+            &["-n", "99999999999999999999999"],
+            "Invalid number of cycles",
+        ),
+        (&["--set", "5=7x"], "Invalid 16-bit value 7x"),
+        (&["--set", "5="], "Invalid 16-bit value"),
+        (&["--set", "5=65536"], "Invalid 16-bit value 65536"),
+        (&["--set", "x=1"], "Invalid number x"),
+    ] {
+        let (_, stderr, success) = run(&program, args);
+        assert!(!success, "{args:?}");
+        assert!(stderr.contains(error), "{args:?}: {stderr}");
+    }
+
+    let (stdout, _, success) = run(&program, &["-n", "1", "--set", "5=-2", "--ram", "5"]);
+    assert!(success);
+    assert!(stdout.starts_with("Stopped after 1 cycles"), "{stdout}");
+    assert!(stdout.contains("RAM[5] = -2"), "{stdout}");
+
+    let mut args = Vec::new();
+    for _ in 0..65 {
+        args.extend(["--ram", "0"]);
+    }
+    let (_, stderr, success) = run(&program, &args[..128]);
+    assert!(success, "{stderr}");
+    let (_, stderr, success) = run(&program, &args);
+    assert!(!success);
+    assert!(
+        stderr.contains("At most 64 --ram options are supported"),
+        "{stderr}"
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
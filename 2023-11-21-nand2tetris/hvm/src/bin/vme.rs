@@ -0,0 +1,111 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use hvm::interpreter::RAM_SIZE;
+use hvm::loader::load;
+use hvm::{Stop, Vm};
+
+const USAGE: &str =
+    "Usage: vme [-n steps] [--set addr=value]... [--ram addr[..end]]... [--input file] file.vm|directory";
+
+// Runs VM programs without translating them, from `Sys.init` or the first function,
+// and prints the same kind of report as `emu` followed by what the program printed.
+// The Jack OS is built in, and `Keyboard` reads the characters of the `--input` file.
+fn main() -> Result<()> {
+    let arguments: Vec<String> = env::args().skip(1).collect();
+
+    let mut max_steps: u64 = 1_000_000;
+    let mut presets: Vec<(usize, u16)> = Vec::new();
+    let mut watched: Vec<(usize, usize)> = Vec::new();
+    let mut input = String::new();
+    let mut file = None;
+
+    let mut args = arguments.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-n" => {
+                let n = args.next().context(USAGE)?;
+                max_steps = n
+                    .parse()
+                    .with_context(|| format!("Invalid number of steps {n}"))?;
+            }
+            "--set" => {
+                let s = args.next().context(USAGE)?;
+                let (address, value) = s
+                    .split_once('=')
+                    .with_context(|| format!("Expect addr=value. Got {s} instead"))?;
+                presets.push((parse_address(address)?, parse_value(value)?));
+            }
+            "--ram" => {
+                let s = args.next().context(USAGE)?;
+                watched.push(match s.split_once("..") {
+                    Some((start, end)) => (parse_address(start)?, parse_address(end)?),
+                    None => {
+                        let address = parse_address(s)?;
+                        (address, address + 1)
+                    }
+                });
+            }
+            "--input" => {
+                let path = args.next().context(USAGE)?;
+                input =
+                    fs::read_to_string(path).with_context(|| format!("Failed to read {path}"))?;
+            }
+            _ if file.is_none() => file = Some(arg),
+            _ => bail!(USAGE),
+        }
+    }
+    let Some(file) = file else {
+        bail!(USAGE);
+    };
+
+    let mut vm = Vm::new(load(Path::new(file))?).map_err(|e| anyhow!(e))?;
+    for (address, value) in presets {
+        vm.ram_mut()[address] = value;
+    }
+    vm.type_text(&input);
+
+    match vm.run(max_steps) {
+        Stop::Halted => println!("Halted after {} steps", vm.steps()),
+        Stop::StepLimit => println!("Stopped after {} steps", vm.steps()),
+        Stop::Error(code) => println!("Sys.error({code}) after {} steps", vm.steps()),
+        Stop::Input => println!("Waiting for keyboard input after {} steps", vm.steps()),
+    }
+    if let Some((function, command)) = vm.current() {
+        match function {
+            "" => println!("At {command}"),
+            function => println!("In {function}: {command}"),
+        }
+    }
+    for (start, end) in watched {
+        for address in start..end {
+            println!("RAM[{address}] = {}", vm.ram()[address] as i16);
+        }
+    }
+    let output = vm.output();
+    if !output.is_empty() {
+        print!("Output:\n{output}");
+    }
+
+    Ok(())
+}
+
+fn parse_address(s: &str) -> Result<usize> {
+    let address = s
+        .parse::<usize>()
+        .with_context(|| format!("Invalid RAM address {s}"))?;
+    if address >= RAM_SIZE {
+        bail!("RAM address {address} is out of range");
+    }
+    Ok(address)
+}
+
+fn parse_value(s: &str) -> Result<u16> {
+    s.parse::<i16>()
+        .map(|v| v as u16)
+        .or_else(|_| s.parse::<u16>())
+        .with_context(|| format!("Invalid 16-bit value {s}"))
+}
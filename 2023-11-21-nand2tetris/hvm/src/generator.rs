@@ -26,10 +26,20 @@ macro_rules! pop_start {
     ])
 }
 
+/// Symbols follow the spec: a function is `{function}`, its labels `{function}${label}`
+/// and statics `{File}.{index}`. VM names cannot contain `$`, so the symbols of the
+/// translator itself use `$$`, e.g. `{function}$$ret{n}` or the routine `$$call`.
 pub struct HackGenerator {
     current_filename: CompactString,
     current_function: CompactString,
-    counter: u16, // ROM is only 32K == 15-bit address
+    counter: usize, // Commands generated so far, which make labels unique
+    shared: bool,
+}
+
+impl Default for HackGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl HackGenerator {
@@ -38,9 +48,16 @@ impl HackGenerator {
             current_filename: "".into(),
             current_function: "".into(), // top-level instruction is in an unnamed function
             counter: 0,
+            shared: false,
         }
     }
 
+    /// Jump to the routines of `routines()` for `call`, `return`, `eq`, `gt` and `lt`
+    /// instead of inlining them
+    pub fn set_shared(&mut self, shared: bool) {
+        self.shared = shared;
+    }
+
     pub fn set_filename(&mut self, s: CompactString) {
         self.current_filename = s;
     }
@@ -62,57 +79,165 @@ M=D
 0;JMP"
     }
 
-    pub fn generate(&mut self, k: Kind) -> SmallVec<[CompactString; 20]> {
+    /// The routines that `call`, `return`, `eq`, `gt` and `lt` jump to in shared mode, to
+    /// be placed after `bootstrap()`. `call` passes the return address in R13, 5 + nargs
+    /// in R14 and the function in D. The comparisons take their return address in D.
+    pub fn routines() -> String {
+        let mut lines: Vec<CompactString> = vec!["($$call)".into(), "@R15\nM=D".into()];
+        lines.extend(
+            [
+                "@R13\nD=M",
+                "@SP\nA=M",
+                "M=D\n@LCL",
+                "D=M\n@SP",
+                "AM=M+1\nM=D",
+                "@ARG\nD=M",
+                "@SP\nAM=M+1",
+                "M=D\n@THIS",
+                "D=M\n@SP",
+                "AM=M+1\nM=D",
+                "@THAT\nD=M",
+                "@SP\nAM=M+1",
+                "M=D\n@SP",
+                "MD=M+1\n@LCL",
+                "M=D\n@R14",
+                "D=D-M\n@ARG",
+                "M=D\n@R15",
+                "A=M\n0;JMP",
+            ]
+            .map(CompactString::from),
+        );
+        lines.push("($$return)".into());
+        lines.extend(Self::return_code());
+        for (routine, code) in [
+            ("eq", Self::equal("$$", "")),
+            ("gt", Self::compare("$$", "", "GT", "JGT")),
+            ("lt", Self::compare("$$", "", "LT", "JLT")),
+        ] {
+            lines.push(format_compact!("($${routine})"));
+            lines.push("@R13\nM=D".into());
+            lines.extend(code);
+            lines.push("@R13\nA=M".into());
+            lines.push("0;JMP".into());
+        }
+        lines.join("\n")
+    }
+
+    // Code of the shared mode that saves a return address in D, jumps to `routine` and
+    // comes back right after
+    fn jump_back(&self, setup: &[CompactString], routine: &str) -> SmallVec<[CompactString; 20]> {
+        let ret = format_compact!("{}$$ret{}", self.current_function, self.counter);
+        let mut code: SmallVec<[CompactString; 20]> = smallvec![format_compact!("@{ret}")];
+        code.push("D=A".into());
+        code.extend(setup.iter().cloned());
+        code.push(format_compact!("@{routine}"));
+        code.push("0;JMP".into());
+        code.push(format_compact!("({ret})"));
+        code
+    }
+
+    /// `eq`. Labels are `{prefix}EQ{kind}{suffix}`.
+    fn equal(prefix: &str, suffix: &str) -> SmallVec<[CompactString; 20]> {
+        let label = |kind: &str| format_compact!("{prefix}EQ{kind}{suffix}");
+        smallvec![
+            "@SP\nAM=M-1".into(),
+            "D=-M\nA=A-1".into(),
+            "D=D+M".into(),
+            format_compact!("@{}", label("s")),
+            "D;JEQ\n@SP".into(),
+            "A=M-1\nM=0".into(),
+            format_compact!("@{}", label("e")),
+            "0;JMP".into(),
+            format_compact!("({})", label("s")),
+            "@SP\nA=M-1".into(),
+            "M=-1".into(),
+            format_compact!("({})", label("e")),
+        ]
+    }
+
+    /// `gt` and `lt`. `x - y` overflows when x and y have different signs, e.g.
+    /// `32767 - (-1)`, so in that case the result only depends on the sign of x.
+    /// Labels are `{prefix}{name}{kind}{suffix}`.
+    fn compare(
+        prefix: &str,
+        suffix: &str,
+        name: &str,
+        jump: &str,
+    ) -> SmallVec<[CompactString; 20]> {
+        let label = |kind: &str| format_compact!("{prefix}{name}{kind}{suffix}");
+        // `s` pushes true, `f` false. Where to go when x is negative and y is not, and the
+        // other way around
+        let (x_negative, y_negative) = match name {
+            "GT" => ("f", "s"),
+            _ => ("s", "f"),
+        };
+        smallvec![
+            "@SP\nAM=M-1".into(),
+            "D=M".into(), // D = y
+            format_compact!("@{}", label("y")),
+            "D;JLT\n@SP".into(),
+            "A=M-1\nD=M".into(), // D = x
+            format_compact!("@{}", label(x_negative)),
+            "D;JLT".into(),
+            // Same signs: x - y does not overflow
+            format_compact!("({})", label("d")),
+            "@SP\nA=M".into(),
+            "D=M\nA=A-1".into(),
+            "D=M-D".into(),
+            format_compact!("@{}", label("s")),
+            format_compact!("D;{jump}"),
+            format_compact!("({})", label("f")),
+            "@SP\nA=M-1".into(),
+            "M=0".into(),
+            format_compact!("@{}", label("e")),
+            "0;JMP".into(),
+            format_compact!("({})", label("y")),
+            "@SP\nA=M-1".into(),
+            "D=M".into(), // D = x
+            format_compact!("@{}", label(y_negative)),
+            "D;JGE".into(),
+            format_compact!("@{}", label("d")),
+            "0;JMP".into(),
+            format_compact!("({})", label("s")),
+            "@SP\nA=M-1".into(),
+            "M=-1".into(),
+            format_compact!("({})", label("e")),
+        ]
+    }
+
+    /// The code of one command. `Kind` can hold a `pop constant`, which the parser
+    /// rejects but other callers may build, so it is an error here too.
+    pub fn generate(&mut self, k: Kind) -> Result<SmallVec<[CompactString; 20]>, String> {
         self.counter += 1;
 
-        match k {
+        Ok(match k {
             Kind::Add => smallvec!["@SP\nAM=M-1".into(), "D=M\nA=A-1".into(), "M=D+M".into(),],
             Kind::Sub => smallvec!["@SP\nAM=M-1".into(), "D=-M\nA=A-1".into(), "M=D+M".into(),],
             Kind::Neg => smallvec!["@SP\nA=M-1".into(), "M=-M".into()],
-            Kind::Eq => smallvec![
-                "@SP\nAM=M-1".into(),
-                "D=-M\nA=A-1".into(),
-                "D=D+M".into(),
-                format_compact!("@{}$EQs{}", self.current_function, self.counter),
-                "D;JEQ\n@SP".into(),
-                "A=M-1\nM=0".into(),
-                format_compact!("@{}$EQe{}", self.current_function, self.counter),
-                "0;JMP".into(),
-                format_compact!("({}$EQs{})", self.current_function, self.counter),
-                "@SP\nA=M-1".into(),
-                "M=-1".into(),
-                format_compact!("({}$EQe{})", self.current_function, self.counter),
-            ],
-            // haven't considered overflow yet
-            Kind::Gt => smallvec![
-                "@SP\nAM=M-1".into(),
-                "D=-M\nA=A-1".into(),
-                "D=D+M".into(),
-                format_compact!("@{}$GTs{}", self.current_function, self.counter),
-                "D;JGT\n@SP".into(),
-                "A=M-1\nM=0".into(),
-                format_compact!("@{}$GTe{}", self.current_function, self.counter),
-                "0;JMP".into(),
-                format_compact!("({}$GTs{})", self.current_function, self.counter),
-                "@SP\nA=M-1".into(),
-                "M=-1".into(),
-                format_compact!("({}$GTe{})", self.current_function, self.counter),
-            ],
-            // haven't considered overflow yet
-            Kind::Lt => smallvec![
-                "@SP\nAM=M-1".into(),
-                "D=-M\nA=A-1".into(),
-                "D=D+M".into(),
-                format_compact!("@{}$LTs{}", self.current_function, self.counter),
-                "D;JLT\n@SP".into(),
-                "A=M-1\nM=0".into(),
-                format_compact!("@{}$LTe{}", self.current_function, self.counter),
-                "0;JMP".into(),
-                format_compact!("({}$LTs{})", self.current_function, self.counter),
-                "@SP\nA=M-1".into(),
-                "M=-1".into(),
-                format_compact!("({}$LTe{})", self.current_function, self.counter),
-            ],
+            Kind::Eq | Kind::Gt | Kind::Lt if self.shared => {
+                let routine = match k {
+                    Kind::Eq => "$$eq",
+                    Kind::Gt => "$$gt",
+                    _ => "$$lt",
+                };
+                self.jump_back(&[], routine)
+            }
+            Kind::Eq => Self::equal(
+                &format_compact!("{}$$", self.current_function),
+                &format_compact!("{}", self.counter),
+            ),
+            Kind::Gt => Self::compare(
+                &format_compact!("{}$$", self.current_function),
+                &format_compact!("{}", self.counter),
+                "GT",
+                "JGT",
+            ),
+            Kind::Lt => Self::compare(
+                &format_compact!("{}$$", self.current_function),
+                &format_compact!("{}", self.counter),
+                "LT",
+                "JLT",
+            ),
             Kind::And => smallvec!["@SP\nAM=M-1".into(), "D=M\nA=A-1".into(), "M=D&M".into(),],
             Kind::Or => smallvec!["@SP\nAM=M-1".into(), "D=M\nA=A-1".into(), "M=D|M".into(),],
             Kind::Not => smallvec!["@SP\nA=M-1".into(), "M=!M".into()],
@@ -202,20 +327,22 @@ M=D
                     format_compact!("@{}", 5 + index),
                     "M=D".into(),
                 ],
-                segment::Segment::Constant => unreachable!(),
+                segment::Segment::Constant => {
+                    return Err(format!("cannot pop to constant (pop constant {index})"))
+                }
             },
 
             Kind::Label(label) => {
-                smallvec![format_compact!("({}.{})", self.current_function, label)]
+                smallvec![format_compact!("({}${})", self.current_function, label)]
             }
             Kind::Goto(label) => smallvec![
-                format_compact!("@{}.{}", self.current_function, label),
+                format_compact!("@{}${}", self.current_function, label),
                 "0;JMP".into()
             ],
             Kind::IfGoto(label) => smallvec![
                 "@SP\nAM=M-1".into(),
                 "D=M".into(),
-                format_compact!("@{}.{}", self.current_function, label),
+                format_compact!("@{}${}", self.current_function, label),
                 "D;JNE".into()
             ],
 
@@ -224,25 +351,35 @@ M=D
                 self.current_function = func;
 
                 if nlcls == 0 {
-                    return smallvec![format_compact!("({})", self.current_function),];
+                    return Ok(smallvec![format_compact!("({})", self.current_function),]);
                 }
 
                 smallvec![
                     format_compact!("({})", self.current_function),
                     format_compact!("@{}", nlcls - 1),
                     "D=A".into(),
-                    format_compact!("({}$init)", self.current_function),
+                    format_compact!("({}$$init)", self.current_function),
                     "@SP\nAM=M+1".into(),
                     "A=A-1\nM=0".into(),
                     "D=D-1".into(),
-                    format_compact!("@{}$init", self.current_function),
+                    format_compact!("@{}$$init", self.current_function),
                     "D;JGE".into(),
                 ]
             }
+            Kind::Call(func, nargs) if self.shared => self.jump_back(
+                &[
+                    "@R13\nM=D".into(),
+                    format_compact!("@{}\nD=A", 5 + nargs),
+                    "@R14\nM=D".into(),
+                    format_compact!("@{}", func),
+                    "D=A".into(),
+                ],
+                "$$call",
+            ),
             Kind::Call(func, nargs) => {
                 // Push return_addr, LCL, ARG, THIS, THAT
                 smallvec![
-                    format_compact!("@{}$ret{}", self.current_function, self.counter),
+                    format_compact!("@{}$$ret{}", self.current_function, self.counter),
                     "D=A\n@SP".into(),
                     "A=M\nM=D".into(),
                     "@LCL\nD=M".into(),
@@ -263,36 +400,39 @@ M=D
                     "@ARG\nM=D".into(),
                     format_compact!("@{}", func),
                     "0;JMP".into(),
-                    format_compact!("({}$ret{})", self.current_function, self.counter),
-                ]
-            }
-            Kind::Return => {
-                smallvec![
-                    "@5\nD=-A".into(),
-                    "@LCL\nA=D+M".into(),
-                    "D=M\n@R13".into(),
-                    // save return_addr to Mem[R13]
-                    // must be done here because *ARG = pop() might overwrite
-                    // return_address if the arguments of the function is 0
-                    "M=D\n@SP".into(),
-                    "A=M-1\nD=M".into(),
-                    "@ARG\nA=M".into(),
-                    "M=D\nD=A".into(),   // set *ARG = pop()
-                    "@SP\nM=D+1".into(), // set SP = ARG + 1
-                    "@LCL\nAM=M-1".into(),
-                    "D=M\n@THAT".into(),
-                    "M=D\n@LCL".into(), // set THAT
-                    "AM=M-1\nD=M".into(),
-                    "@THIS\nM=D".into(), // set THIS
-                    "@LCL\nAM=M-1".into(),
-                    "D=M\n@ARG".into(),
-                    "M=D\n@LCL".into(), // set ARG
-                    "A=M-1\nD=M".into(),
-                    "@LCL\nM=D".into(), // set LCL
-                    "@R13\nA=M".into(),
-                    "0;JMP".into(), // ret
+                    format_compact!("({}$$ret{})", self.current_function, self.counter),
                 ]
             }
-        }
+            Kind::Return if self.shared => smallvec!["@$$return\n0;JMP".into()],
+            Kind::Return => Self::return_code(),
+        })
+    }
+
+    fn return_code() -> SmallVec<[CompactString; 20]> {
+        smallvec![
+            "@5\nD=-A".into(),
+            "@LCL\nA=D+M".into(),
+            "D=M\n@R13".into(),
+            // save return_addr to Mem[R13]
+            // must be done here because *ARG = pop() might overwrite
+            // return_address if the arguments of the function is 0
+            "M=D\n@SP".into(),
+            "A=M-1\nD=M".into(),
+            "@ARG\nA=M".into(),
+            "M=D\nD=A".into(),   // set *ARG = pop()
+            "@SP\nM=D+1".into(), // set SP = ARG + 1
+            "@LCL\nAM=M-1".into(),
+            "D=M\n@THAT".into(),
+            "M=D\n@LCL".into(), // set THAT
+            "AM=M-1\nD=M".into(),
+            "@THIS\nM=D".into(), // set THIS
+            "@LCL\nAM=M-1".into(),
+            "D=M\n@ARG".into(),
+            "M=D\n@LCL".into(), // set ARG
+            "A=M-1\nD=M".into(),
+            "@LCL\nM=D".into(), // set LCL
+            "@R13\nA=M".into(),
+            "0;JMP".into(), // ret
+        ]
     }
 }
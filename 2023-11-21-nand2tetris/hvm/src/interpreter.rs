@@ -0,0 +1,409 @@
+use std::collections::HashMap;
+
+use compact_str::{format_compact, CompactString};
+
+use crate::os::{builtin, Builtin, Os};
+use crate::parser::Kind;
+use crate::segment::Segment;
+
+pub const RAM_SIZE: usize = 32768;
+
+// RAM addresses of the VM pointers and segments, as laid out by `HackGenerator`
+const SP: usize = 0;
+const LCL: usize = 1;
+const ARG: usize = 2;
+const THIS: usize = 3;
+const THAT: usize = 4;
+const TEMP: usize = 5;
+// The assembler allocates variables, so statics, from RAM[16]
+const STATIC_START: usize = 16;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Stop {
+    /// The program reached a loop such as `label END goto END`, the function the VM
+    /// started from returned, or the program ran past its last command
+    Halted,
+    /// The requested number of commands was executed
+    StepLimit,
+    /// `Sys.error` was called with this code, by the program or by a built-in
+    Error(i16),
+    /// `Keyboard` needs more characters than were typed
+    Input,
+}
+
+#[derive(Clone, Copy)]
+enum Location {
+    Constant(u16),
+    /// `static`, `pointer` and `temp`
+    Fixed(usize),
+    /// Offset from the address held by a pointer: `argument`, `local`, `this` and `that`
+    Based(usize, u16),
+}
+
+// A command with its labels, functions and statics resolved
+#[derive(Clone, Copy)]
+enum Op {
+    Add,
+    Sub,
+    Neg,
+    Eq,
+    Gt,
+    Lt,
+    And,
+    Or,
+    Not,
+    Push(Location),
+    Pop(Location),
+    Label,
+    Goto(usize),
+    IfGoto(usize),
+    /// A `goto` to itself, possibly through labels
+    Halt,
+    Function(u16),
+    Call(usize, u16),
+    /// A call to the OS, with the number of arguments
+    Native(Builtin, u16),
+    Return,
+}
+
+/// Executes VM commands directly over a modelled RAM. Segments, statics and call frames
+/// live at the same addresses as in the code `HackGenerator` emits, so a program leaves
+/// RAM as its translation would.
+///
+/// Calls to OS functions that no file defines go to the native `Os`. A program with
+/// `Main.main` and without `Sys.init` starts as with the OS's `Sys.init`, by calling
+/// `Main.main` and halting when it returns.
+pub struct Vm {
+    ops: Vec<Op>,
+    // Function and text of every command
+    commands: Vec<(CompactString, String)>,
+    ram: Box<[u16]>,
+    pc: usize,
+    // Calls that have not returned yet
+    depth: usize,
+    steps: u64,
+    os: Os,
+}
+
+impl Vm {
+    /// Load `files`, each given with its name without `.vm`, and prepare to start from
+    /// `Sys.init`, then `Main.main`, then the first function. SP, LCL and ARG are set as
+    /// by `HackGenerator::bootstrap`.
+    pub fn new(files: Vec<(CompactString, Vec<Kind>)>) -> Result<Vm, String> {
+        // Addresses of functions and labels. Labels are local to their function.
+        let mut functions: HashMap<CompactString, usize> = HashMap::new();
+        let mut labels: HashMap<(CompactString, CompactString), usize> = HashMap::new();
+        let mut function = CompactString::default();
+        let mut index = 0;
+        for (_, kinds) in &files {
+            for kind in kinds {
+                match kind {
+                    Kind::Function(name, _) => {
+                        if functions.insert(name.clone(), index).is_some() {
+                            return Err(format!("function {name} is defined twice"));
+                        }
+                        function = name.clone();
+                    }
+                    Kind::Label(label) => {
+                        labels.insert((function.clone(), label.clone()), index);
+                    }
+                    _ => {}
+                }
+                index += 1;
+            }
+        }
+        let mut ops = Vec::with_capacity(index);
+        let mut commands = Vec::with_capacity(index);
+        let mut statics: HashMap<CompactString, usize> = HashMap::new();
+        let mut function = CompactString::default();
+        for (file, kinds) in files {
+            for kind in kinds {
+                if let Kind::Function(name, _) = &kind {
+                    function = name.clone();
+                }
+                let context = || format!("{function}: {kind}");
+                let label = |label: &CompactString| {
+                    labels
+                        .get(&(function.clone(), label.clone()))
+                        .copied()
+                        .ok_or_else(|| format!("{}: unknown label {label}", context()))
+                };
+                let mut location = |segment: &Segment, index: u16| match segment {
+                    Segment::Constant => Location::Constant(index),
+                    Segment::Static => {
+                        let next = STATIC_START + statics.len();
+                        let name = format_compact!("{file}.{index}");
+                        Location::Fixed(*statics.entry(name).or_insert(next))
+                    }
+                    Segment::Pointer => Location::Fixed(THIS + index as usize),
+                    Segment::Temp => Location::Fixed(TEMP + index as usize),
+                    Segment::Argument => Location::Based(ARG, index),
+                    Segment::Local => Location::Based(LCL, index),
+                    Segment::This => Location::Based(THIS, index),
+                    Segment::That => Location::Based(THAT, index),
+                };
+
+                let op = match &kind {
+                    Kind::Add => Op::Add,
+                    Kind::Sub => Op::Sub,
+                    Kind::Neg => Op::Neg,
+                    Kind::Eq => Op::Eq,
+                    Kind::Gt => Op::Gt,
+                    Kind::Lt => Op::Lt,
+                    Kind::And => Op::And,
+                    Kind::Or => Op::Or,
+                    Kind::Not => Op::Not,
+                    Kind::Push(segment, index) => Op::Push(location(segment, *index)),
+                    Kind::Pop(Segment::Constant, _) => {
+                        return Err(format!("{}: cannot pop to constant", context()))
+                    }
+                    Kind::Pop(segment, index) => Op::Pop(location(segment, *index)),
+                    Kind::Label(_) => Op::Label,
+                    Kind::Goto(name) => {
+                        let target = label(name)?;
+                        let here = ops.len();
+                        if target <= here && ops[target..].iter().all(|op| matches!(op, Op::Label))
+                        {
+                            Op::Halt
+                        } else {
+                            Op::Goto(target)
+                        }
+                    }
+                    Kind::IfGoto(name) => Op::IfGoto(label(name)?),
+                    Kind::Function(_, nlcls) => Op::Function(*nlcls),
+                    Kind::Call(name, nargs) => match (functions.get(name), builtin(name)) {
+                        (Some(target), _) => Op::Call(*target, *nargs),
+                        (None, Some((builtin, n))) if n == *nargs => Op::Native(builtin, n),
+                        (None, Some((_, n))) => {
+                            return Err(format!("{}: {name} takes {n} arguments", context()))
+                        }
+                        (None, None) => {
+                            return Err(format!("{}: unknown function {name}", context()))
+                        }
+                    },
+                    Kind::Return => Op::Return,
+                };
+                ops.push(op);
+                commands.push((function.clone(), kind.to_string()));
+            }
+        }
+
+        let start = match (functions.get("Sys.init"), functions.get("Main.main")) {
+            (Some(start), _) => *start,
+            (None, Some(main)) => {
+                let start = ops.len();
+                ops.extend([Op::Call(*main, 0), Op::Pop(Location::Fixed(TEMP)), Op::Halt]);
+                for command in ["call Main.main 0", "pop temp 0", "call Sys.halt 0"] {
+                    commands.push(("Sys.init".into(), command.to_string()));
+                }
+                start
+            }
+            (None, None) => ops
+                .iter()
+                .position(|op| matches!(op, Op::Function(_)))
+                .unwrap_or(0),
+        };
+        if ops.len() > u16::MAX as usize {
+            return Err(format!(
+                "{} commands do not fit in 16-bit return addresses",
+                ops.len()
+            ));
+        }
+
+        let mut ram = vec![0; RAM_SIZE].into_boxed_slice();
+        ram[SP] = 261;
+        ram[LCL] = 261;
+        ram[ARG] = 256;
+
+        Ok(Vm {
+            ops,
+            commands,
+            ram,
+            pc: start,
+            depth: 0,
+            steps: 0,
+            os: Os::new(),
+        })
+    }
+
+    pub fn ram(&self) -> &[u16] {
+        &self.ram
+    }
+
+    pub fn ram_mut(&mut self) -> &mut [u16] {
+        &mut self.ram
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Queue characters for `Keyboard`
+    pub fn type_text(&mut self, text: &str) {
+        self.os.type_text(text);
+    }
+
+    /// What the program printed with `Output`
+    pub fn output(&self) -> String {
+        self.os.output()
+    }
+
+    /// The function the next command belongs to and the command, e.g.
+    /// `("Main.fib", "push argument 0")`. `None` past the last command.
+    pub fn current(&self) -> Option<(&str, &str)> {
+        self.commands
+            .get(self.pc)
+            .map(|(function, command)| (function.as_str(), command.as_str()))
+    }
+
+    /// Execute at most `max_steps` commands
+    pub fn run(&mut self, max_steps: u64) -> Stop {
+        for _ in 0..max_steps {
+            if let Some(stop) = self.step() {
+                return stop;
+            }
+        }
+        Stop::StepLimit
+    }
+
+    /// Execute one command. Nothing is done when the program cannot go on, and the
+    /// reason is returned instead.
+    pub fn step(&mut self) -> Option<Stop> {
+        let Some(&op) = self.ops.get(self.pc) else {
+            return Some(Stop::Halted);
+        };
+        let mut next = self.pc + 1;
+        match op {
+            Op::Add => self.binary(|x, y| x.wrapping_add(y)),
+            Op::Sub => self.binary(|x, y| x.wrapping_sub(y)),
+            Op::And => self.binary(|x, y| x & y),
+            Op::Or => self.binary(|x, y| x | y),
+            Op::Eq => self.binary(|x, y| truth(x == y)),
+            Op::Gt => self.binary(|x, y| truth(x as i16 > y as i16)),
+            Op::Lt => self.binary(|x, y| truth((x as i16) < y as i16)),
+            Op::Neg => {
+                let value = self.pop();
+                self.push(value.wrapping_neg());
+            }
+            Op::Not => {
+                let value = self.pop();
+                self.push(!value);
+            }
+            Op::Push(location) => {
+                let value = match location {
+                    Location::Constant(value) => value,
+                    _ => self.ram[self.address(location)],
+                };
+                self.push(value);
+            }
+            Op::Pop(location) => {
+                let value = self.pop();
+                let address = self.address(location);
+                self.ram[address] = value;
+            }
+            Op::Label => {}
+            Op::Goto(target) => next = target,
+            Op::IfGoto(target) => {
+                if self.pop() != 0 {
+                    next = target;
+                }
+            }
+            Op::Halt => return Some(Stop::Halted),
+            Op::Function(nlcls) => {
+                for _ in 0..nlcls {
+                    self.push(0);
+                }
+            }
+            Op::Call(target, nargs) => {
+                let sp = self.ram[SP];
+                self.push(next as u16);
+                for pointer in [LCL, ARG, THIS, THAT] {
+                    self.push(self.ram[pointer]);
+                }
+                self.ram[ARG] = sp.wrapping_sub(nargs);
+                self.ram[LCL] = self.ram[SP];
+                self.depth += 1;
+                next = target;
+            }
+            Op::Native(builtin, nargs) => {
+                // Arguments stay on the stack if the call does not complete
+                let sp = self.ram[SP];
+                let base = sp.wrapping_sub(nargs);
+                let mut args = [0; 4];
+                for (i, arg) in args.iter_mut().take(nargs as usize).enumerate() {
+                    *arg = self.ram[wrap(base.wrapping_add(i as u16))];
+                }
+                match self
+                    .os
+                    .call(builtin, &args[..nargs as usize], &mut self.ram)
+                {
+                    Ok(value) => {
+                        self.ram[SP] = base;
+                        self.push(value);
+                    }
+                    Err(stop) => return Some(stop),
+                }
+            }
+            Op::Return => {
+                if self.depth == 0 {
+                    return Some(Stop::Halted);
+                }
+                self.depth -= 1;
+                let frame = self.ram[LCL];
+                let saved = |offset: u16| wrap(frame.wrapping_sub(offset));
+                let return_address = self.ram[saved(5)];
+                let value = self.pop();
+                let arg = self.ram[ARG];
+                self.ram[wrap(arg)] = value;
+                self.ram[SP] = arg.wrapping_add(1);
+                for (offset, pointer) in [(1, THAT), (2, THIS), (3, ARG), (4, LCL)] {
+                    self.ram[pointer] = self.ram[saved(offset)];
+                }
+                next = return_address as usize;
+            }
+        }
+        self.pc = next;
+        self.steps += 1;
+        None
+    }
+
+    fn push(&mut self, value: u16) {
+        let sp = self.ram[SP];
+        self.ram[wrap(sp)] = value;
+        self.ram[SP] = sp.wrapping_add(1);
+    }
+
+    fn pop(&mut self) -> u16 {
+        let sp = self.ram[SP].wrapping_sub(1);
+        self.ram[SP] = sp;
+        self.ram[wrap(sp)]
+    }
+
+    // Pop y then x, and push f(x, y)
+    fn binary(&mut self, f: impl Fn(u16, u16) -> u16) {
+        let y = self.pop();
+        let x = self.pop();
+        self.push(f(x, y));
+    }
+
+    fn address(&self, location: Location) -> usize {
+        match location {
+            Location::Constant(_) => unreachable!("constants have no address"),
+            Location::Fixed(address) => wrap(address as u16),
+            Location::Based(pointer, index) => wrap(self.ram[pointer].wrapping_add(index)),
+        }
+    }
+}
+
+// The Hack computer ignores the 16th bit of addresses
+fn wrap(address: u16) -> usize {
+    (address & 0x7FFF) as usize
+}
+
+fn truth(b: bool) -> u16 {
+    if b {
+        0xFFFF
+    } else {
+        0
+    }
+}
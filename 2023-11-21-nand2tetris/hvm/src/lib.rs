@@ -0,0 +1,8 @@
+pub mod generator;
+pub mod interpreter;
+pub mod loader;
+pub mod os;
+pub mod parser;
+pub mod segment;
+
+pub use interpreter::{Stop, Vm};
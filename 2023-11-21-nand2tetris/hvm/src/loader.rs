@@ -0,0 +1,114 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use asm::diagnostic::{Diagnostic, Diagnostics, Error};
+use compact_str::CompactString;
+
+use crate::parser::{parse, Kind, STATIC_SIZE};
+use crate::segment::Segment;
+
+/// The commands of a `.vm` file, or of every `.vm` file of a directory, along with the
+/// name of their file without `.vm`. Files are sorted by name with `Sys.vm` first, so
+/// that the order does not depend on the file system. Errors of all the files are
+/// reported together as `Diagnostics`.
+pub fn load(path: &Path) -> Result<Vec<(CompactString, Vec<Kind>)>> {
+    let mut paths = if path.is_dir() {
+        let entries = fs::read_dir(path)
+            .with_context(|| format!("Failed to read directory {}", path.display()))?;
+        let mut paths = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if !path.is_dir() && path.extension().is_some_and(|ext| ext == "vm") {
+                paths.push(path);
+            }
+        }
+        paths
+    } else if path.extension().is_some_and(|ext| ext == "vm") {
+        vec![path.to_path_buf()]
+    } else {
+        bail!(
+            "Input file must be .vm file or a directory. Got {} instead",
+            path.display()
+        );
+    };
+    paths.sort_by(|a, b| {
+        let not_sys = |path: &Path| path.file_stem().is_some_and(|stem| stem != "Sys");
+        (not_sys(a), a).cmp(&(not_sys(b), b))
+    });
+
+    let mut files = Vec::new();
+    let mut diagnostics = Diagnostics::new();
+    let mut statics = HashSet::new();
+    // Labels are local to their function, which may go on in the next file like in
+    // the generated code
+    let mut labels = HashSet::new();
+    let mut function = CompactString::default();
+    for path in paths {
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let name: CompactString = path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into();
+        let file = path.display().to_string();
+        let located = |index: usize, line: &str, error: Error| {
+            let mut diagnostic = Diagnostic::new(index, line, error);
+            diagnostic.file = file.clone();
+            diagnostic
+        };
+
+        let mut kinds = Vec::new();
+        for (index, line) in contents.lines().enumerate() {
+            let kind = match parse(line) {
+                Ok(Some(kind)) => kind,
+                Ok(None) => continue,
+                Err(error) => {
+                    diagnostics.push(located(index, line, error));
+                    continue;
+                }
+            };
+            if let Kind::Push(Segment::Static, i) | Kind::Pop(Segment::Static, i) = kind {
+                // Reported once, at the first static that does not fit
+                if statics.insert((name.clone(), i)) && statics.len() == STATIC_SIZE as usize + 1 {
+                    let text = i.to_string();
+                    let error = Error::new(
+                        line.rfind(&text).unwrap_or(0),
+                        &text,
+                        format!(
+                            "too many statics: {name}.{i} is static number {}, but only \
+                             {STATIC_SIZE} fit in RAM[16..256]",
+                            statics.len()
+                        ),
+                    );
+                    diagnostics.push(located(index, line, error));
+                }
+            }
+            match &kind {
+                Kind::Function(name, _) => function = name.clone(),
+                Kind::Label(label) if !labels.insert((function.clone(), label.clone())) => {
+                    let error = Error::new(
+                        line.rfind(label.as_str()).unwrap_or(0),
+                        label,
+                        match function.as_str() {
+                            "" => format!("duplicate label `{label}` outside of functions"),
+                            function => format!("duplicate label `{label}` in `{function}`"),
+                        },
+                    );
+                    diagnostics.push(located(index, line, error));
+                }
+                _ => {}
+            }
+            kinds.push(kind);
+        }
+        files.push((name, kinds));
+    }
+
+    if diagnostics.is_empty() {
+        Ok(files)
+    } else {
+        Err(diagnostics.into())
+    }
+}
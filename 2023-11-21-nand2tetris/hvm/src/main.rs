@@ -1,112 +1,112 @@
-use std::collections::HashMap;
 use std::env;
+use std::fmt::Write;
 use std::fs;
-use std::io::BufWriter;
-use std::io::Write;
 use std::path::Path;
 
+use anyhow::{bail, Context, Result};
 use compact_str::CompactString;
-use generator::HackGenerator;
-use parser::parse;
-use parser::Kind;
+use hvm::generator::HackGenerator;
+use hvm::loader::load;
+use hvm::parser::Kind;
 
-mod generator;
-mod parser;
-mod segment;
+const USAGE: &str = "Usage: hvm [--shared] [--size] file.vm|directory";
 
-fn main() {
-    let arguments: Vec<CompactString> = env::args_os()
-        .map(|arg| arg.to_str().unwrap().into())
-        .collect();
-    assert!(
-        arguments.len() == 2,
-        "Usage: {} file",
-        arguments.get(0).unwrap_or(&"hvm".into())
-    );
-
-    let is_single_file = arguments[1].ends_with(".vm");
-    let path = Path::new(&arguments[1]);
-    assert!(
-        !(!is_single_file && !path.is_dir()),
-        "Input file must be .vm file or a directory. Got {} instead",
-        arguments[1]
-    );
-
-    if is_single_file {
-        if let Some(c) = path.file_name() {
-            if !c
-                .to_str()
-                .unwrap()
-                .chars()
-                .next()
-                .unwrap()
-                .is_ascii_uppercase()
-            {
-                panic!(
-                    "Input filename must start with an uppercase character. Got {} instead",
-                    arguments[1]
-                );
-            }
-        } else {
-            panic!("Invalid input file path");
+fn main() -> Result<()> {
+    let mut shared = false;
+    let mut size_report = false;
+    let mut file = None;
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--shared" => shared = true,
+            "--size" => size_report = true,
+            _ if file.is_none() => file = Some(arg),
+            _ => bail!(USAGE),
         }
     }
-
-    let instructions: HashMap<CompactString, Vec<Kind>> = {
-        if is_single_file {
-            let contents = fs::read_to_string(path).unwrap();
-            HashMap::from([(
-                path.file_stem().unwrap().to_str().unwrap().into(),
-                contents.lines().filter_map(parse).collect(),
-            )])
-        } else {
-            fs::read_dir(path)
-                .unwrap()
-                .map(Result::unwrap)
-                .map(|entry| entry.path())
-                .filter(|path| !path.is_dir() && path.extension().is_some_and(|s| s == "vm"))
-                .map(|filepath| {
-                    let filename = filepath.file_stem().unwrap().to_str().unwrap();
-                    assert!(
-                        filename.chars().next().unwrap().is_ascii_uppercase(),
-                        "Input filename must start with an uppercase character. Got {} instead",
-                        arguments[1]
-                    );
-                    let contents = fs::read_to_string(&filepath).unwrap();
-                    (
-                        filename.into(),
-                        contents.lines().filter_map(parse).collect::<Vec<Kind>>(),
-                    )
-                })
-                .collect()
-        }
+    let Some(file) = file else {
+        bail!(USAGE);
     };
 
-    let path = {
-        let mut pathbuf = path.to_path_buf();
-        if !is_single_file {
-            pathbuf.push(path.file_name().unwrap());
+    let path = Path::new(&file);
+    let instructions = load(path)?;
+    for (filename, _) in &instructions {
+        if !filename.starts_with(|c: char| c.is_ascii_uppercase()) {
+            bail!(
+                "Input filename must start with an uppercase character. Got {filename}.vm instead"
+            );
         }
-        pathbuf.set_extension("asm");
-        pathbuf
+    }
+
+    // `dir/` is translated into `dir/dir.asm`
+    let out_path = if path.is_dir() {
+        path.join(path.file_name().unwrap_or_default())
+            .with_extension("asm")
+    } else {
+        path.with_extension("asm")
     };
 
-    let mut writer = BufWriter::new(
-        fs::File::options()
-            .write(true)
-            .truncate(true)
-            .create(true)
-            .open(path)
-            .unwrap(),
-    );
+    if size_report {
+        print!("{}", report(&instructions)?);
+    }
 
+    let mut out = String::new();
     let mut generator = HackGenerator::new();
-    writeln!(&mut writer, "{}", HackGenerator::bootstrap()).unwrap();
+    generator.set_shared(shared);
+    writeln!(out, "{}", HackGenerator::bootstrap())?;
+    if shared {
+        writeln!(out, "{}", HackGenerator::routines())?;
+    }
     for (filename, instructions) in instructions {
         generator.set_filename(filename);
         for ins in instructions {
-            writeln!(&mut writer, "// {ins}").unwrap();
-            writeln!(&mut writer, "{}", generator.generate(ins).join("\n")).unwrap();
+            writeln!(out, "// {ins}")?;
+            let code = generator.generate(ins).map_err(anyhow::Error::msg)?;
+            writeln!(out, "{}", code.join("\n"))?;
         }
     }
+    fs::write(&out_path, out).with_context(|| format!("Failed to write {}", out_path.display()))
+}
+
+// Number of instructions, leaving out labels
+fn count(code: &str) -> usize {
+    code.lines().filter(|line| !line.starts_with('(')).count()
+}
+
+// Instructions spent on calls, returns, comparisons, shared routines and everything
+// else, in the inline and in the shared mode
+fn report(instructions: &[(CompactString, Vec<Kind>)]) -> Result<String> {
+    let rows = ["call", "return", "compare", "routines", "other"];
+    let mut sizes = [[0; 2]; 5];
+    for (mode, shared) in [false, true].into_iter().enumerate() {
+        let mut generator = HackGenerator::new();
+        generator.set_shared(shared);
+        sizes[4][mode] += count(HackGenerator::bootstrap());
+        if shared {
+            sizes[3][mode] += count(&HackGenerator::routines());
+        }
+        for (filename, instructions) in instructions {
+            generator.set_filename(filename.clone());
+            for ins in instructions {
+                let row = match ins {
+                    Kind::Call(..) => 0,
+                    Kind::Return => 1,
+                    Kind::Eq | Kind::Gt | Kind::Lt => 2,
+                    _ => 4,
+                };
+                let code = generator
+                    .generate(ins.clone())
+                    .map_err(anyhow::Error::msg)?;
+                sizes[row][mode] += count(&code.join("\n"));
+            }
+        }
+    }
+
+    let mut out = format!("{:10}{:>8}{:>8}\n", "", "inline", "shared");
+    for (name, [inline, shared]) in rows.iter().zip(sizes) {
+        out += &format!("{name:10}{inline:>8}{shared:>8}\n");
+    }
+    let inline: usize = sizes.iter().map(|s| s[0]).sum();
+    let shared: usize = sizes.iter().map(|s| s[1]).sum();
+    out += &format!("{:10}{inline:>8}{shared:>8}\n", "total");
+    Ok(out)
 }
@@ -0,0 +1,592 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::interpreter::Stop;
+
+// The heap of the official OS
+const HEAP_START: u16 = 2048;
+const HEAP_END: u16 = 16384;
+
+const SCREEN: usize = 16384;
+const KBD: usize = 24576;
+const WIDTH: i16 = 512;
+const HEIGHT: i16 = 256;
+
+// Characters with a special meaning for `Output` and `Keyboard`
+const NEW_LINE: u16 = 128;
+const BACKSPACE: u16 = 129;
+const DOUBLE_QUOTE: u16 = 34;
+
+// Size of the `Output` text grid
+const ROWS: usize = 23;
+const COLUMNS: usize = 64;
+
+// Sys.error codes of the official OS
+const WAIT_NEGATIVE: i16 = 1;
+const ARRAY_SIZE: i16 = 2;
+const DIVIDE_BY_ZERO: i16 = 3;
+const SQRT_NEGATIVE: i16 = 4;
+const ALLOC_SIZE: i16 = 5;
+const HEAP_OVERFLOW: i16 = 6;
+const PIXEL_COORDINATES: i16 = 7;
+const LINE_COORDINATES: i16 = 8;
+const RECTANGLE_COORDINATES: i16 = 9;
+const CIRCLE_CENTER: i16 = 12;
+const CIRCLE_RADIUS: i16 = 13;
+const STRING_LENGTH: i16 = 14;
+const CHAR_AT_INDEX: i16 = 15;
+const SET_CHAR_AT_INDEX: i16 = 16;
+const STRING_FULL: i16 = 17;
+const STRING_EMPTY: i16 = 18;
+const SET_INT_CAPACITY: i16 = 19;
+const CURSOR_POSITION: i16 = 20;
+
+/// A function of the Jack OS implemented natively
+#[derive(Clone, Copy)]
+pub enum Builtin {
+    MathInit,
+    MathAbs,
+    MathMultiply,
+    MathDivide,
+    MathMin,
+    MathMax,
+    MathSqrt,
+    MemoryInit,
+    MemoryPeek,
+    MemoryPoke,
+    MemoryAlloc,
+    MemoryDeAlloc,
+    StringNew,
+    StringDispose,
+    StringLength,
+    StringCharAt,
+    StringSetCharAt,
+    StringAppendChar,
+    StringEraseLastChar,
+    StringIntValue,
+    StringSetInt,
+    StringBackSpace,
+    StringDoubleQuote,
+    StringNewLine,
+    ArrayNew,
+    ArrayDispose,
+    OutputInit,
+    OutputMoveCursor,
+    OutputPrintChar,
+    OutputPrintString,
+    OutputPrintInt,
+    OutputPrintln,
+    OutputBackSpace,
+    ScreenInit,
+    ScreenClearScreen,
+    ScreenSetColor,
+    ScreenDrawPixel,
+    ScreenDrawLine,
+    ScreenDrawRectangle,
+    ScreenDrawCircle,
+    KeyboardInit,
+    KeyboardKeyPressed,
+    KeyboardReadChar,
+    KeyboardReadLine,
+    KeyboardReadInt,
+    SysHalt,
+    SysError,
+    SysWait,
+}
+
+// Name and number of arguments of every built-in. Methods take `this` first.
+const BUILTINS: [(&str, u16, Builtin); 48] = [
+    ("Math.init", 0, Builtin::MathInit),
+    ("Math.abs", 1, Builtin::MathAbs),
+    ("Math.multiply", 2, Builtin::MathMultiply),
+    ("Math.divide", 2, Builtin::MathDivide),
+    ("Math.min", 2, Builtin::MathMin),
+    ("Math.max", 2, Builtin::MathMax),
+    ("Math.sqrt", 1, Builtin::MathSqrt),
+    ("Memory.init", 0, Builtin::MemoryInit),
+    ("Memory.peek", 1, Builtin::MemoryPeek),
+    ("Memory.poke", 2, Builtin::MemoryPoke),
+    ("Memory.alloc", 1, Builtin::MemoryAlloc),
+    ("Memory.deAlloc", 1, Builtin::MemoryDeAlloc),
+    ("String.new", 1, Builtin::StringNew),
+    ("String.dispose", 1, Builtin::StringDispose),
+    ("String.length", 1, Builtin::StringLength),
+    ("String.charAt", 2, Builtin::StringCharAt),
+    ("String.setCharAt", 3, Builtin::StringSetCharAt),
+    ("String.appendChar", 2, Builtin::StringAppendChar),
+    ("String.eraseLastChar", 1, Builtin::StringEraseLastChar),
+    ("String.intValue", 1, Builtin::StringIntValue),
+    ("String.setInt", 2, Builtin::StringSetInt),
+    ("String.backSpace", 0, Builtin::StringBackSpace),
+    ("String.doubleQuote", 0, Builtin::StringDoubleQuote),
+    ("String.newLine", 0, Builtin::StringNewLine),
+    ("Array.new", 1, Builtin::ArrayNew),
+    ("Array.dispose", 1, Builtin::ArrayDispose),
+    ("Output.init", 0, Builtin::OutputInit),
+    ("Output.moveCursor", 2, Builtin::OutputMoveCursor),
+    ("Output.printChar", 1, Builtin::OutputPrintChar),
+    ("Output.printString", 1, Builtin::OutputPrintString),
+    ("Output.printInt", 1, Builtin::OutputPrintInt),
+    ("Output.println", 0, Builtin::OutputPrintln),
+    ("Output.backSpace", 0, Builtin::OutputBackSpace),
+    ("Screen.init", 0, Builtin::ScreenInit),
+    ("Screen.clearScreen", 0, Builtin::ScreenClearScreen),
+    ("Screen.setColor", 1, Builtin::ScreenSetColor),
+    ("Screen.drawPixel", 2, Builtin::ScreenDrawPixel),
+    ("Screen.drawLine", 4, Builtin::ScreenDrawLine),
+    ("Screen.drawRectangle", 4, Builtin::ScreenDrawRectangle),
+    ("Screen.drawCircle", 3, Builtin::ScreenDrawCircle),
+    ("Keyboard.init", 0, Builtin::KeyboardInit),
+    ("Keyboard.keyPressed", 0, Builtin::KeyboardKeyPressed),
+    ("Keyboard.readChar", 0, Builtin::KeyboardReadChar),
+    ("Keyboard.readLine", 1, Builtin::KeyboardReadLine),
+    ("Keyboard.readInt", 1, Builtin::KeyboardReadInt),
+    ("Sys.halt", 0, Builtin::SysHalt),
+    ("Sys.error", 1, Builtin::SysError),
+    ("Sys.wait", 1, Builtin::SysWait),
+];
+
+/// The built-in called `name` and its number of arguments
+pub fn builtin(name: &str) -> Option<(Builtin, u16)> {
+    BUILTINS
+        .iter()
+        .find(|(n, _, _)| *n == name)
+        .map(|(_, nargs, builtin)| (*builtin, *nargs))
+}
+
+/// State of the native OS. Objects live in the VM's RAM: the heap is RAM[2048..16384]
+/// and a string at `s` holds its capacity at `s`, its length at `s + 1` and its
+/// characters from `s + 2`.
+///
+/// `Output` keeps a grid of characters instead of drawing glyphs on the screen, and
+/// `Keyboard` reads from a queue of typed characters.
+pub struct Os {
+    // Free and allocated heap blocks by address, with their size
+    free: BTreeMap<u16, u16>,
+    used: BTreeMap<u16, u16>,
+    black: bool,
+    text: Vec<u8>,
+    row: usize,
+    column: usize,
+    input: VecDeque<u16>,
+}
+
+impl Os {
+    pub fn new() -> Os {
+        Os {
+            free: BTreeMap::from([(HEAP_START, HEAP_END - HEAP_START)]),
+            used: BTreeMap::new(),
+            black: true,
+            text: vec![b' '; ROWS * COLUMNS],
+            row: 0,
+            column: 0,
+            input: VecDeque::new(),
+        }
+    }
+
+    /// Queue characters for `Keyboard`. A newline is the Jack newline key.
+    pub fn type_text(&mut self, text: &str) {
+        self.input.extend(text.chars().map(|c| match c {
+            '\n' => NEW_LINE,
+            c => c as u16,
+        }));
+    }
+
+    /// The rows printed by `Output`, without trailing blanks
+    pub fn output(&self) -> String {
+        let mut lines: Vec<String> = self
+            .text
+            .chunks(COLUMNS)
+            .map(|row| String::from_utf8_lossy(row).trim_end().to_string())
+            .collect();
+        while lines.last().is_some_and(|line| line.is_empty()) {
+            lines.pop();
+        }
+        lines.iter().map(|line| format!("{line}\n")).collect()
+    }
+
+    /// Call `builtin` with `args`. Fails with the reason to stop, having changed nothing
+    /// when `Keyboard` needs more input.
+    pub fn call(&mut self, builtin: Builtin, args: &[u16], ram: &mut [u16]) -> Result<u16, Stop> {
+        let arg = |i: usize| args[i] as i16;
+        let value = match builtin {
+            Builtin::MathInit
+            | Builtin::MemoryInit
+            | Builtin::OutputInit
+            | Builtin::ScreenInit
+            | Builtin::KeyboardInit => 0,
+            Builtin::MathAbs => arg(0).wrapping_abs() as u16,
+            Builtin::MathMultiply => arg(0).wrapping_mul(arg(1)) as u16,
+            Builtin::MathDivide => {
+                if arg(1) == 0 {
+                    return Err(self.error(DIVIDE_BY_ZERO));
+                }
+                arg(0).wrapping_div(arg(1)) as u16
+            }
+            Builtin::MathMin => arg(0).min(arg(1)) as u16,
+            Builtin::MathMax => arg(0).max(arg(1)) as u16,
+            Builtin::MathSqrt => {
+                if arg(0) < 0 {
+                    return Err(self.error(SQRT_NEGATIVE));
+                }
+                (arg(0) as f64).sqrt() as u16
+            }
+
+            Builtin::MemoryPeek => ram[address(args[0])],
+            Builtin::MemoryPoke => {
+                ram[address(args[0])] = args[1];
+                0
+            }
+            Builtin::MemoryAlloc => self.alloc(arg(0), ALLOC_SIZE)?,
+            Builtin::MemoryDeAlloc | Builtin::StringDispose | Builtin::ArrayDispose => {
+                self.deallocate(args[0]);
+                0
+            }
+            Builtin::ArrayNew => self.alloc(arg(0), ARRAY_SIZE)?,
+
+            Builtin::StringNew => {
+                if arg(0) < 0 {
+                    return Err(self.error(STRING_LENGTH));
+                }
+                let s = self.alloc(arg(0).saturating_add(2), ALLOC_SIZE)?;
+                ram[address(s)] = args[0];
+                ram[field(s, 1)] = 0;
+                s
+            }
+            Builtin::StringLength => ram[field(args[0], 1)],
+            Builtin::StringCharAt => {
+                let s = args[0];
+                if !(0..ram[field(s, 1)] as i16).contains(&arg(1)) {
+                    return Err(self.error(CHAR_AT_INDEX));
+                }
+                ram[field(s, 2 + args[1])]
+            }
+            Builtin::StringSetCharAt => {
+                let s = args[0];
+                if !(0..ram[field(s, 1)] as i16).contains(&arg(1)) {
+                    return Err(self.error(SET_CHAR_AT_INDEX));
+                }
+                ram[field(s, 2 + args[1])] = args[2];
+                0
+            }
+            Builtin::StringAppendChar => {
+                let s = args[0];
+                let length = ram[field(s, 1)];
+                if length >= ram[address(s)] {
+                    return Err(self.error(STRING_FULL));
+                }
+                ram[field(s, 2 + length)] = args[1];
+                ram[field(s, 1)] = length + 1;
+                s
+            }
+            Builtin::StringEraseLastChar => {
+                let s = args[0];
+                let length = ram[field(s, 1)];
+                if length == 0 {
+                    return Err(self.error(STRING_EMPTY));
+                }
+                ram[field(s, 1)] = length - 1;
+                0
+            }
+            Builtin::StringIntValue => int_value(&string(ram, args[0])),
+            Builtin::StringSetInt => {
+                let s = args[0];
+                let digits = arg(1).to_string();
+                if digits.len() > ram[address(s)] as usize {
+                    return Err(self.error(SET_INT_CAPACITY));
+                }
+                for (i, c) in digits.bytes().enumerate() {
+                    ram[field(s, 2 + i as u16)] = c as u16;
+                }
+                ram[field(s, 1)] = digits.len() as u16;
+                0
+            }
+            Builtin::StringBackSpace => BACKSPACE,
+            Builtin::StringDoubleQuote => DOUBLE_QUOTE,
+            Builtin::StringNewLine => NEW_LINE,
+
+            Builtin::OutputMoveCursor => {
+                let (row, column) = (arg(0), arg(1));
+                if !(0..ROWS as i16).contains(&row) || !(0..COLUMNS as i16).contains(&column) {
+                    return Err(self.error(CURSOR_POSITION));
+                }
+                self.row = row as usize;
+                self.column = column as usize;
+                0
+            }
+            Builtin::OutputPrintChar => {
+                self.print_char(args[0]);
+                0
+            }
+            Builtin::OutputPrintString => {
+                for c in string(ram, args[0]) {
+                    self.print_char(c as u16);
+                }
+                0
+            }
+            Builtin::OutputPrintInt => {
+                self.print(&arg(0).to_string());
+                0
+            }
+            Builtin::OutputPrintln => {
+                self.print_char(NEW_LINE);
+                0
+            }
+            Builtin::OutputBackSpace => {
+                self.print_char(BACKSPACE);
+                0
+            }
+
+            Builtin::ScreenClearScreen => {
+                ram[SCREEN..KBD].fill(0);
+                0
+            }
+            Builtin::ScreenSetColor => {
+                self.black = args[0] != 0;
+                0
+            }
+            Builtin::ScreenDrawPixel => {
+                if !on_screen(arg(0), arg(1)) {
+                    return Err(self.error(PIXEL_COORDINATES));
+                }
+                self.draw_pixel(ram, arg(0), arg(1));
+                0
+            }
+            Builtin::ScreenDrawLine => {
+                let (x1, y1, x2, y2) = (arg(0), arg(1), arg(2), arg(3));
+                if !on_screen(x1, y1) || !on_screen(x2, y2) {
+                    return Err(self.error(LINE_COORDINATES));
+                }
+                self.draw_line(ram, x1, y1, x2, y2);
+                0
+            }
+            Builtin::ScreenDrawRectangle => {
+                let (x1, y1, x2, y2) = (arg(0), arg(1), arg(2), arg(3));
+                if !on_screen(x1, y1) || !on_screen(x2, y2) || x1 > x2 || y1 > y2 {
+                    return Err(self.error(RECTANGLE_COORDINATES));
+                }
+                for y in y1..=y2 {
+                    self.draw_line(ram, x1, y, x2, y);
+                }
+                0
+            }
+            Builtin::ScreenDrawCircle => {
+                let (x, y, r) = (arg(0), arg(1), arg(2));
+                if !on_screen(x, y) {
+                    return Err(self.error(CIRCLE_CENTER));
+                }
+                if !(0..=181).contains(&r) {
+                    return Err(self.error(CIRCLE_RADIUS));
+                }
+                // Pixels off the screen are left out
+                for dy in -r..=r {
+                    let dx = ((r as i32 * r as i32 - dy as i32 * dy as i32) as f64).sqrt() as i16;
+                    let row = y + dy;
+                    if (0..HEIGHT).contains(&row) {
+                        let (left, right) = ((x - dx).max(0), (x + dx).min(WIDTH - 1));
+                        self.draw_line(ram, left, row, right, row);
+                    }
+                }
+                0
+            }
+
+            Builtin::KeyboardKeyPressed => ram[KBD],
+            Builtin::KeyboardReadChar => {
+                let c = self.input.pop_front().ok_or(Stop::Input)?;
+                self.print_char(c);
+                c
+            }
+            Builtin::KeyboardReadLine | Builtin::KeyboardReadInt => {
+                let Some(end) = self.input.iter().position(|c| *c == NEW_LINE) else {
+                    return Err(Stop::Input);
+                };
+                self.print(&String::from_utf8_lossy(&string(ram, args[0])));
+                let mut line = Vec::new();
+                for c in self.input.drain(..=end).collect::<Vec<u16>>() {
+                    self.print_char(c);
+                    match c {
+                        NEW_LINE => {}
+                        BACKSPACE => {
+                            line.pop();
+                        }
+                        c => line.push(c as u8),
+                    }
+                }
+                if let Builtin::KeyboardReadInt = builtin {
+                    int_value(&line)
+                } else {
+                    let s = self.alloc(line.len() as i16 + 2, ALLOC_SIZE)?;
+                    ram[address(s)] = line.len() as u16;
+                    ram[field(s, 1)] = line.len() as u16;
+                    for (i, c) in line.iter().enumerate() {
+                        ram[field(s, 2 + i as u16)] = *c as u16;
+                    }
+                    s
+                }
+            }
+
+            Builtin::SysHalt => return Err(Stop::Halted),
+            Builtin::SysError => return Err(self.error(arg(0))),
+            Builtin::SysWait => {
+                if arg(0) < 0 {
+                    return Err(self.error(WAIT_NEGATIVE));
+                }
+                0
+            }
+        };
+        Ok(value)
+    }
+
+    // Print `ERR<code>` as the official `Sys.error` does
+    fn error(&mut self, code: i16) -> Stop {
+        self.print(&format!("ERR{code}"));
+        Stop::Error(code)
+    }
+
+    fn alloc(&mut self, size: i16, error: i16) -> Result<u16, Stop> {
+        if size <= 0 {
+            return Err(self.error(error));
+        }
+        let size = size as u16;
+        let Some((&start, &length)) = self.free.iter().find(|(_, length)| **length >= size) else {
+            return Err(self.error(HEAP_OVERFLOW));
+        };
+        self.free.remove(&start);
+        if length > size {
+            self.free.insert(start + size, length - size);
+        }
+        self.used.insert(start, size);
+        Ok(start)
+    }
+
+    // Blocks that were not allocated are ignored
+    fn deallocate(&mut self, start: u16) {
+        let Some(mut size) = self.used.remove(&start) else {
+            return;
+        };
+        let mut start = start;
+        if let Some(next) = self.free.remove(&(start + size)) {
+            size += next;
+        }
+        if let Some((&previous, &length)) = self.free.range(..start).next_back() {
+            if previous + length == start {
+                self.free.remove(&previous);
+                start = previous;
+                size += length;
+            }
+        }
+        self.free.insert(start, size);
+    }
+
+    fn print(&mut self, s: &str) {
+        for c in s.bytes() {
+            self.print_char(c as u16);
+        }
+    }
+
+    // The cursor wraps to the next row, and from the last row to the first one
+    fn print_char(&mut self, c: u16) {
+        match c {
+            NEW_LINE => {
+                self.column = 0;
+                self.row = (self.row + 1) % ROWS;
+            }
+            BACKSPACE => {
+                if self.column > 0 {
+                    self.column -= 1;
+                } else if self.row > 0 {
+                    self.row -= 1;
+                    self.column = COLUMNS - 1;
+                }
+                self.text[self.row * COLUMNS + self.column] = b' ';
+            }
+            c => {
+                let c = if (32..127).contains(&c) {
+                    c as u8
+                } else {
+                    b'?'
+                };
+                self.text[self.row * COLUMNS + self.column] = c;
+                self.column += 1;
+                if self.column == COLUMNS {
+                    self.print_char(NEW_LINE);
+                }
+            }
+        }
+    }
+
+    fn draw_pixel(&self, ram: &mut [u16], x: i16, y: i16) {
+        let word = &mut ram[SCREEN + y as usize * 32 + x as usize / 16];
+        let bit = 1 << (x % 16);
+        if self.black {
+            *word |= bit;
+        } else {
+            *word &= !bit;
+        }
+    }
+
+    // Bresenham's algorithm, between two points on the screen
+    fn draw_line(&self, ram: &mut [u16], x1: i16, y1: i16, x2: i16, y2: i16) {
+        let (dx, dy) = ((x2 - x1).abs(), -(y2 - y1).abs());
+        let (sx, sy) = ((x2 - x1).signum(), (y2 - y1).signum());
+        let (mut x, mut y, mut error) = (x1, y1, dx + dy);
+        loop {
+            self.draw_pixel(ram, x, y);
+            if x == x2 && y == y2 {
+                break;
+            }
+            if 2 * error >= dy {
+                error += dy;
+                x += sx;
+            }
+            if 2 * error <= dx {
+                error += dx;
+                y += sy;
+            }
+        }
+    }
+}
+
+impl Default for Os {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn address(a: u16) -> usize {
+    (a & 0x7FFF) as usize
+}
+
+fn field(s: u16, offset: u16) -> usize {
+    address(s.wrapping_add(offset))
+}
+
+fn on_screen(x: i16, y: i16) -> bool {
+    (0..WIDTH).contains(&x) && (0..HEIGHT).contains(&y)
+}
+
+// The characters of the string at `s`
+fn string(ram: &[u16], s: u16) -> Vec<u8> {
+    let length = ram[address(s.wrapping_add(1))];
+    (0..length)
+        .map(|i| ram[address(s.wrapping_add(2 + i))] as u8)
+        .collect()
+}
+
+// Leading digits, after an optional `-`, as `String.intValue` reads them
+fn int_value(chars: &[u8]) -> u16 {
+    let (negative, digits) = match chars.split_first() {
+        Some((b'-', rest)) => (true, rest),
+        _ => (false, chars),
+    };
+    let value = digits
+        .iter()
+        .take_while(|c| c.is_ascii_digit())
+        .fold(0i16, |value, c| {
+            value.wrapping_mul(10).wrapping_add((c - b'0') as i16)
+        });
+    if negative {
+        value.wrapping_neg() as u16
+    } else {
+        value as u16
+    }
+}
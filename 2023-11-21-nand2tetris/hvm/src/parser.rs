@@ -1,11 +1,13 @@
 use std::str::FromStr;
 
+use asm::diagnostic::Error;
+use asm::symbol_tables::is_predefined;
 use compact_str::CompactString;
 use strum_macros::{EnumString, IntoStaticStr};
 
 use crate::segment::Segment;
 
-#[derive(EnumString, IntoStaticStr)]
+#[derive(Clone, EnumString, IntoStaticStr)]
 pub enum Kind {
     #[strum(serialize = "add")]
     Add,
@@ -65,70 +67,148 @@ impl std::fmt::Display for Kind {
     }
 }
 
-// Assume line is a valid VM program
-//
-// No whitespace: logic, arithmetic, return
-// 1 whitespace: label, goto, if-goto
-// 2 whitespaces: push, pop, function, call
-pub fn parse(line: &str) -> Option<Kind> {
-    let comment_start = line.find("//").unwrap_or(line.len());
-    let line = line[..comment_start].trim();
-    if line.is_empty() {
-        return None;
+// The predefined segments that are not based on a pointer
+const POINTER_SIZE: u16 = 2;
+const TEMP_SIZE: u16 = 8;
+/// Statics are assembler variables, in RAM[16..256]
+pub const STATIC_SIZE: u16 = 240;
+// `push constant` becomes an A-instruction, which holds 15 bits
+const CONSTANT_MAX: u16 = 32767;
+
+/// Parse one line of a VM file. Blank lines and comments give `None`.
+pub fn parse(line: &str) -> Result<Option<Kind>, Error> {
+    let code = &line[..line.find("//").unwrap_or(line.len())];
+    // Words with their column
+    let words: Vec<(usize, &str)> = code
+        .split_whitespace()
+        .map(|word| (word.as_ptr() as usize - line.as_ptr() as usize, word))
+        .collect();
+    let Some(&(column, op)) = words.first() else {
+        return Ok(None);
+    };
+
+    // Arithmetic commands and `return` take no arguments
+    let (form, simple) = match op {
+        "push" | "pop" => ("segment index", None),
+        "function" => ("name nlocals", None),
+        "call" => ("name nargs", None),
+        "label" | "goto" | "if-goto" => ("label", None),
+        _ => match Kind::from_str(op) {
+            Ok(kind) => ("", Some(kind)),
+            Err(_) => return Err(Error::new(column, op, format!("unknown command `{op}`"))),
+        },
+    };
+    let arity = form.split_whitespace().count();
+    if words.len() != arity + 1 {
+        let (column, text) = words.get(arity + 1).copied().unwrap_or((column, op));
+        let usage = format!("{op} {form}");
+        return Err(Error::new(
+            column,
+            text,
+            format!("expect `{}`", usage.trim_end()),
+        ));
     }
 
-    // check 1/2 whitespaces
-    if let Some((op, other)) = line.split_once(' ') {
-        let other = other.trim_start();
-
-        // 2 whitespaces
-        if let Some((left, right)) = other.split_once(' ') {
-            let right = right.trim_start();
-            return Some(match op {
-                "push" | "pop" => {
-                    // always assume this is a valid index after parsing
-                    // let assembler handle this error
-                    let index = right.parse::<u16>().unwrap_or_else(|_| {
-                        panic!("Invalid index for push/pop instruction. Got {line}")
-                    });
-
-                    match op {
-                        "push" => Kind::Push(parse_segment(left), index),
-                        "pop" => Kind::Pop(parse_segment(left), index),
-                        _ => unreachable!(),
-                    }
-                }
-                "function" => {
-                    let nlcls = right.parse::<u16>().unwrap_or_else(|_| {
-                        panic!(
-                            "Invalid number of local variables for function instruction. Got {line}"
-                        )
-                    });
-                    Kind::Function(left.into(), nlcls)
-                }
-                "call" => {
-                    let nargs = right.parse::<u16>().unwrap_or_else(|_| {
-                        panic!("Invalid number of arguments for call instruction. Got {line}")
-                    });
-                    Kind::Call(left.into(), nargs)
+    let number = |i: usize, what: &str| {
+        let (column, word) = words[i];
+        word.parse::<u16>()
+            .map_err(|_| Error::new(column, word, format!("invalid {what} `{word}`")))
+    };
+    let name = |i: usize, valid: fn(&str) -> bool, what: &str| {
+        let (column, word) = words[i];
+        if valid(word) {
+            Ok(CompactString::from(word))
+        } else {
+            Err(Error::new(column, word, format!("invalid {what} `{word}`")))
+        }
+    };
+    let function = |i: usize| {
+        let name = name(i, is_function, "function name")?;
+        let (column, word) = words[i];
+        // `@SP` would address the stack pointer instead of the function
+        if is_predefined(word) {
+            return Err(Error::new(
+                column,
+                word,
+                format!("function name `{word}` is a predefined symbol of the assembler"),
+            ));
+        }
+        // `File.index` is the symbol of a static variable
+        match name.rsplit_once('.') {
+            Some((file, index))
+                if !index.is_empty() && index.bytes().all(|b| b.is_ascii_digit()) =>
+            {
+                Err(Error::new(
+                    column,
+                    word,
+                    format!("function name `{word}` is the symbol of static {index} of {file}.vm"),
+                ))
+            }
+            _ => Ok(name),
+        }
+    };
+    let label = |i: usize| name(i, is_label, "label");
+
+    Ok(Some(match op {
+        "push" | "pop" => {
+            let (segment_column, segment_word) = words[1];
+            let segment = Segment::from_str(segment_word).map_err(|_| {
+                Error::new(
+                    segment_column,
+                    segment_word,
+                    format!("unknown segment `{segment_word}`"),
+                )
+            })?;
+            let index = number(2, "index")?;
+            let (index_column, index_word) = words[2];
+            let limit = match segment {
+                Segment::Pointer => Some(POINTER_SIZE - 1),
+                Segment::Temp => Some(TEMP_SIZE - 1),
+                Segment::Static => Some(STATIC_SIZE - 1),
+                Segment::Constant => Some(CONSTANT_MAX),
+                _ => None,
+            };
+            if let Some(limit) = limit.filter(|limit| index > *limit) {
+                return Err(Error::new(
+                    index_column,
+                    index_word,
+                    match segment {
+                        Segment::Constant => format!("constant must be between 0 and {limit}"),
+                        segment => format!("{segment} index must be between 0 and {limit}"),
+                    },
+                ));
+            }
+            match (op, segment) {
+                ("pop", Segment::Constant) => {
+                    return Err(Error::new(
+                        segment_column,
+                        segment_word,
+                        "cannot pop to constant".to_string(),
+                    ))
                 }
-                _ => unreachable!(),
-            });
+                ("push", segment) => Kind::Push(segment, index),
+                (_, segment) => Kind::Pop(segment, index),
+            }
         }
+        "function" => Kind::Function(function(1)?, number(2, "number of local variables")?),
+        "call" => Kind::Call(function(1)?, number(2, "number of arguments")?),
+        "label" => Kind::Label(label(1)?),
+        "goto" => Kind::Goto(label(1)?),
+        "if-goto" => Kind::IfGoto(label(1)?),
+        _ => return Ok(simple),
+    }))
+}
 
-        // 1 whitespace
-        return Some(match op {
-            "label" => Kind::Label(other.into()),
-            "goto" => Kind::Goto(other.into()),
-            "if-goto" => Kind::IfGoto(other.into()),
-            _ => unreachable!(),
-        });
-    }
-
-    // arithmetic, return
-    Kind::from_str(line).ok()
+// Letters, digits, `_`, `.` and `:`. `$` is left out: the translator joins a function
+// and its labels with `$`, and keeps `$$` for its own symbols.
+fn is_label(word: &str) -> bool {
+    !word.is_empty()
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_.:".contains(c))
 }
 
-fn parse_segment(segment: &str) -> Segment {
-    Segment::from_str(segment).expect("Failed to parse segment")
+// A label that does not start with a digit, as the assembler requires
+fn is_function(word: &str) -> bool {
+    is_label(word) && !word.starts_with(|c: char| c.is_ascii_digit())
 }
@@ -1,6 +1,6 @@
 use strum_macros::{Display, EnumString};
 
-#[derive(EnumString, Display)]
+#[derive(Clone, EnumString, Display)]
 #[strum(serialize_all = "lowercase")]
 pub enum Segment {
     Argument,
@@ -0,0 +1,12 @@
+// Runs Sys.vm, translated by hvm into Compare.asm, and checks gt and lt on
+// boundary values. See Sys.vm for the layout of the results.
+
+load Compare.asm,
+output-file Compare.out,
+compare-to Compare.cmp,
+output-list RAM[3000]%B1.7.1 RAM[3001]%B1.7.1 RAM[3002]%B1.7.1 RAM[3003]%B1.7.1 RAM[3004]%B1.7.1 RAM[3005]%B1.7.1 RAM[3006]%B1.7.1 RAM[3007]%B1.7.1 RAM[3008]%B1.7.1 RAM[3009]%B1.7.1 RAM[3010]%B1.7.1 RAM[3011]%B1.7.1 RAM[3012]%B1.7.1 RAM[3013]%B1.7.1;
+
+while RAM[3014] = 0 {
+    ticktock;
+}
+output;
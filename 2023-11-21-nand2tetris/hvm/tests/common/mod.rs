@@ -0,0 +1,34 @@
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use hvm::generator::HackGenerator;
+
+/// `test/name`, a directory of VM files
+pub fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("test")
+        .join(name)
+}
+
+/// The program in `path` translated to assembly, as `hvm` does with `--shared` if `shared`
+pub fn translate(path: &Path, shared: bool) -> String {
+    let mut out = String::new();
+    writeln!(out, "{}", HackGenerator::bootstrap()).unwrap();
+    if shared {
+        writeln!(out, "{}", HackGenerator::routines()).unwrap();
+    }
+    let mut generator = HackGenerator::new();
+    generator.set_shared(shared);
+    for (filename, instructions) in hvm::loader::load(path).unwrap() {
+        generator.set_filename(filename);
+        for instruction in instructions {
+            writeln!(
+                out,
+                "{}",
+                generator.generate(instruction).unwrap().join("\n")
+            )
+            .unwrap();
+        }
+    }
+    out
+}
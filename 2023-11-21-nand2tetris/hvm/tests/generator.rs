@@ -0,0 +1,44 @@
+mod common;
+
+use std::fs;
+
+use hvm::generator::HackGenerator;
+use hvm::parser::Kind;
+use hvm::segment::Segment;
+
+#[test]
+fn pop_constant_is_an_error() {
+    let mut generator = HackGenerator::new();
+    let error = generator
+        .generate(Kind::Pop(Segment::Constant, 1))
+        .unwrap_err();
+    assert_eq!(error, "cannot pop to constant (pop constant 1)");
+    assert!(generator.generate(Kind::Pop(Segment::Temp, 1)).is_ok());
+}
+
+#[test]
+fn comparisons_do_not_overflow() {
+    let fixture = common::fixture("Compare");
+    let dir = std::env::temp_dir().join(format!("hvm-compare-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    for file in ["Compare.tst", "Compare.cmp"] {
+        fs::copy(fixture.join(file), dir.join(file)).unwrap();
+    }
+    fs::write(dir.join("Compare.asm"), common::translate(&fixture, false)).unwrap();
+
+    let outcome = tst::run(&dir.join("Compare.tst")).unwrap();
+    assert!(outcome.mismatch.is_none(), "{}", outcome.output);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn labels_stay_unique_past_65535_commands() {
+    let mut generator = HackGenerator::new();
+    for _ in 0..65535 {
+        generator.generate(Kind::Add).unwrap();
+    }
+    let code = generator.generate(Kind::Eq).unwrap().join("\n");
+    assert!(code.contains("($$EQs65536)"), "{code}");
+    let code = generator.generate(Kind::Eq).unwrap().join("\n");
+    assert!(code.contains("($$EQs65537)"), "{code}");
+}
@@ -0,0 +1,59 @@
+mod common;
+
+use std::process::Command;
+
+use emu::cpu::Computer;
+use hvm::{Stop, Vm};
+
+// The rows of RAM[3000..3014] in Compare.cmp
+fn expected_compare() -> Vec<u16> {
+    let cmp = std::fs::read_to_string(common::fixture("Compare").join("Compare.cmp")).unwrap();
+    let row = cmp.lines().nth(1).unwrap();
+    row.split('|')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| u16::from_str_radix(s, 2).unwrap())
+        .collect()
+}
+
+#[test]
+fn programs_leave_ram_as_their_translation() {
+    let path = common::fixture("Compare");
+    let mut vm = Vm::new(hvm::loader::load(&path).unwrap()).unwrap();
+    assert_eq!(vm.run(1_000_000), Stop::Halted);
+    assert_eq!(vm.ram()[3000..3014], expected_compare());
+    assert_eq!(vm.ram()[3014], 1);
+
+    let mut computer = Computer::new(
+        &asm::assemble(&common::translate(&path, false))
+            .unwrap()
+            .words,
+    );
+    assert_eq!(computer.run(1_000_000), emu::Stop::Halted);
+    // R13 to R15 are scratch registers of the generated code
+    assert_eq!(vm.ram()[..13], computer.ram()[..13]);
+    assert_eq!(vm.ram()[16..16384], computer.ram()[16..16384]);
+}
+
+#[test]
+fn vme_runs_directories() {
+    let output = Command::new(env!("CARGO_BIN_EXE_vme"))
+        .args(["--ram", "3000..3015"])
+        .arg(common::fixture("Compare"))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    let mut lines = stdout.lines();
+    assert_eq!(lines.next(), Some("Halted after 1098 steps"));
+    assert_eq!(lines.next(), Some("In Sys.init: goto END"));
+    let mut expected = expected_compare();
+    expected.push(1);
+    for (i, value) in expected.into_iter().enumerate() {
+        assert_eq!(
+            lines.next().unwrap(),
+            format!("RAM[{}] = {value}", 3000 + i)
+        );
+    }
+}
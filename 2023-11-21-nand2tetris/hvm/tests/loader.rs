@@ -0,0 +1,76 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+use hvm::loader::load;
+
+const FILES: [(&str, &str); 4] = [
+    ("Zeta.vm", "function Zeta.f 0\npush static 0\nreturn\n"),
+    ("Main.vm", "function Main.main 0\npush static 0\nreturn\n"),
+    (
+        "Sys.vm",
+        "function Sys.init 0\ncall Main.main 0\nlabel END\ngoto END\n",
+    ),
+    ("Alpha.vm", "function Alpha.f 0\npush static 0\nreturn\n"),
+];
+
+// A `Prog` directory with `FILES`, written in the given order, and entries that are not
+// VM files. The directory names the output, so it is the same for every test.
+fn setup(name: &str, order: [usize; 4]) -> PathBuf {
+    let dir = std::env::temp_dir()
+        .join(format!("hvm-loader-{}-{name}", std::process::id()))
+        .join("Prog");
+    fs::create_dir_all(dir.join("Nested.vm")).unwrap();
+    fs::write(dir.join("notes.txt"), "push constant 1").unwrap();
+    for i in order {
+        let (file, contents) = FILES[i];
+        fs::write(dir.join(file), contents).unwrap();
+    }
+    dir
+}
+
+#[test]
+fn sys_comes_first_then_files_by_name() {
+    let dir = setup("order", [0, 1, 2, 3]);
+    let files = load(&dir).unwrap();
+    fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+    let names: Vec<&str> = files.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, ["Sys", "Alpha", "Main", "Zeta"]);
+}
+
+#[test]
+fn output_does_not_depend_on_the_directory_order() {
+    let mut outputs = Vec::new();
+    for (name, order) in [("forward", [0, 1, 2, 3]), ("backward", [3, 2, 1, 0])] {
+        let dir = setup(name, order);
+        let output = Command::new(env!("CARGO_BIN_EXE_hvm"))
+            .arg(&dir)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        outputs.push(fs::read_to_string(dir.join("Prog.asm")).unwrap());
+        fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+    }
+    assert_eq!(outputs[0], outputs[1]);
+
+    // Sys.init right after the bootstrap code, then the files by name
+    let first = outputs[0].lines().find(|line| line.starts_with("// "));
+    assert_eq!(first, Some("// function Sys.init 0"));
+    let statics: Vec<&str> = outputs[0]
+        .lines()
+        .filter(|line| line.ends_with(".0") && line.starts_with('@'))
+        .collect();
+    assert_eq!(statics, ["@Alpha.0", "@Main.0", "@Zeta.0"]);
+}
+
+#[test]
+fn errors_name_their_file() {
+    let dir = setup("errors", [0, 1, 2, 3]);
+    fs::write(dir.join("Bad.vm"), "function Bad.f 0\npush constnt 1\n").unwrap();
+    let error = load(&dir).err().unwrap().to_string();
+    fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+
+    let location = format!("{}:2:6: error", dir.join("Bad.vm").display());
+    assert!(error.contains(&location), "{error}");
+    assert!(error.ends_with("aborting due to 1 error"), "{error}");
+}
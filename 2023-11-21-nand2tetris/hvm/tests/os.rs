@@ -0,0 +1,176 @@
+use hvm::os::{Builtin, Os};
+use hvm::Stop;
+
+fn ram() -> Vec<u16> {
+    vec![0; 32768]
+}
+
+fn pixel(ram: &[u16], x: usize, y: usize) -> bool {
+    ram[16384 + y * 32 + x / 16] >> (x % 16) & 1 == 1
+}
+
+// The set pixels, by row
+fn pixels(ram: &[u16]) -> Vec<(usize, usize)> {
+    let mut pixels = Vec::new();
+    for y in 0..256 {
+        for x in 0..512 {
+            if pixel(ram, x, y) {
+                pixels.push((x, y));
+            }
+        }
+    }
+    pixels
+}
+
+#[test]
+fn freed_blocks_are_reused() {
+    let (mut os, mut ram) = (Os::new(), ram());
+    let a = os.call(Builtin::MemoryAlloc, &[10], &mut ram).unwrap();
+    let b = os.call(Builtin::MemoryAlloc, &[5], &mut ram).unwrap();
+    assert_eq!((a, b), (2048, 2058));
+
+    os.call(Builtin::MemoryDeAlloc, &[a], &mut ram).unwrap();
+    assert_eq!(os.call(Builtin::MemoryAlloc, &[4], &mut ram), Ok(2048));
+    assert_eq!(os.call(Builtin::MemoryAlloc, &[6], &mut ram), Ok(2052));
+    // Too large for the hole left by `a`
+    assert_eq!(os.call(Builtin::MemoryAlloc, &[1], &mut ram), Ok(2063));
+}
+
+#[test]
+fn freed_neighbours_are_coalesced() {
+    let (mut os, mut ram) = (Os::new(), ram());
+    let blocks: Vec<u16> = (0..3)
+        .map(|_| os.call(Builtin::MemoryAlloc, &[10], &mut ram).unwrap())
+        .collect();
+    let end = os.call(Builtin::MemoryAlloc, &[1], &mut ram).unwrap();
+    assert_eq!(end, 2078);
+
+    // Merged with the next block, then with the previous one
+    os.call(Builtin::MemoryDeAlloc, &[blocks[1]], &mut ram)
+        .unwrap();
+    os.call(Builtin::MemoryDeAlloc, &[blocks[2]], &mut ram)
+        .unwrap();
+    os.call(Builtin::MemoryDeAlloc, &[blocks[0]], &mut ram)
+        .unwrap();
+    assert_eq!(os.call(Builtin::MemoryAlloc, &[30], &mut ram), Ok(2048));
+}
+
+#[test]
+fn exhausted_heaps_are_an_error() {
+    let (mut os, mut ram) = (Os::new(), ram());
+    assert_eq!(
+        os.call(Builtin::MemoryAlloc, &[16384 - 2048], &mut ram),
+        Ok(2048)
+    );
+    assert_eq!(
+        os.call(Builtin::MemoryAlloc, &[1], &mut ram),
+        Err(Stop::Error(6))
+    );
+    assert_eq!(
+        os.call(Builtin::MemoryAlloc, &[0], &mut ram),
+        Err(Stop::Error(5))
+    );
+    assert_eq!(os.output(), "ERR6ERR5\n");
+
+    os.call(Builtin::MemoryDeAlloc, &[2048], &mut ram).unwrap();
+    assert_eq!(os.call(Builtin::MemoryAlloc, &[1], &mut ram), Ok(2048));
+}
+
+#[test]
+fn strings_grow_up_to_their_capacity() {
+    let (mut os, mut ram) = (Os::new(), ram());
+    let s = os.call(Builtin::StringNew, &[2], &mut ram).unwrap();
+    assert_eq!(
+        os.call(Builtin::StringAppendChar, &[s, 'h' as u16], &mut ram),
+        Ok(s)
+    );
+    assert_eq!(
+        os.call(Builtin::StringAppendChar, &[s, 'i' as u16], &mut ram),
+        Ok(s)
+    );
+    assert_eq!(os.call(Builtin::StringLength, &[s], &mut ram), Ok(2));
+    assert_eq!(
+        os.call(Builtin::StringCharAt, &[s, 1], &mut ram),
+        Ok('i' as u16)
+    );
+    assert_eq!(
+        os.call(Builtin::StringAppendChar, &[s, '!' as u16], &mut ram),
+        Err(Stop::Error(17))
+    );
+}
+
+#[test]
+fn set_int_writes_signed_digits() {
+    let (mut os, mut ram) = (Os::new(), ram());
+    let s = os.call(Builtin::StringNew, &[6], &mut ram).unwrap();
+    os.call(Builtin::StringSetInt, &[s, -32768i16 as u16], &mut ram)
+        .unwrap();
+    os.call(Builtin::OutputPrintString, &[s], &mut ram).unwrap();
+    assert_eq!(os.call(Builtin::StringLength, &[s], &mut ram), Ok(6));
+    assert_eq!(
+        os.call(Builtin::StringIntValue, &[s], &mut ram),
+        Ok(-32768i16 as u16)
+    );
+
+    os.call(Builtin::StringSetInt, &[s, 42], &mut ram).unwrap();
+    assert_eq!(os.call(Builtin::StringIntValue, &[s], &mut ram), Ok(42));
+
+    let small = os.call(Builtin::StringNew, &[2], &mut ram).unwrap();
+    assert_eq!(
+        os.call(Builtin::StringSetInt, &[small, 100], &mut ram),
+        Err(Stop::Error(19))
+    );
+    assert_eq!(os.output(), "-32768ERR19\n");
+}
+
+#[test]
+fn steep_lines_have_one_pixel_per_row() {
+    let (mut os, mut ram) = (Os::new(), ram());
+    os.call(Builtin::ScreenDrawLine, &[3, 0, 5, 20], &mut ram)
+        .unwrap();
+    let pixels = pixels(&ram);
+    assert_eq!(pixels.len(), 21);
+    for (y, (x, row)) in pixels.iter().enumerate() {
+        assert_eq!(*row, y);
+        assert!((3..=5).contains(x));
+    }
+    assert_eq!(pixels[0], (3, 0));
+    assert_eq!(pixels[20], (5, 20));
+}
+
+#[test]
+fn lines_with_negative_slopes_join_their_ends() {
+    for (x1, y1, x2, y2) in [(0, 10, 30, 0), (30, 0, 0, 10), (20, 0, 17, 40)] {
+        let (mut os, mut ram) = (Os::new(), ram());
+        os.call(Builtin::ScreenDrawLine, &[x1, y1, x2, y2], &mut ram)
+            .unwrap();
+        let mut pixels = pixels(&ram);
+        let (dx, dy) = (x1.abs_diff(x2), y1.abs_diff(y2));
+        assert_eq!(pixels.len(), dx.max(dy) as usize + 1);
+        assert!(pixel(&ram, x1 as usize, y1 as usize));
+        assert!(pixel(&ram, x2 as usize, y2 as usize));
+        // One pixel per step along the longer axis, each touching the previous one
+        if dx > dy {
+            pixels.sort();
+        }
+        for pair in pixels.windows(2) {
+            let ((xa, ya), (xb, yb)) = (pair[0], pair[1]);
+            let steps = if dx > dy {
+                (xb - xa, ya.abs_diff(yb))
+            } else {
+                (yb - ya, xa.abs_diff(xb))
+            };
+            assert!(steps.0 == 1 && steps.1 <= 1, "{pair:?}");
+        }
+    }
+}
+
+#[test]
+fn lines_off_the_screen_are_an_error() {
+    let (mut os, mut ram) = (Os::new(), ram());
+    assert_eq!(
+        os.call(Builtin::ScreenDrawLine, &[0, 0, 512, 0], &mut ram),
+        Err(Stop::Error(8))
+    );
+    assert!(pixels(&ram).is_empty());
+}
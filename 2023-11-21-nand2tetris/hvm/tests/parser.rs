@@ -0,0 +1,31 @@
+use hvm::parser::parse;
+
+fn error(line: &str) -> String {
+    parse(line).err().unwrap().message
+}
+
+#[test]
+fn predefined_symbols_are_not_function_names() {
+    for name in [
+        "SP", "LCL", "ARG", "THIS", "THAT", "R0", "R15", "SCREEN", "KBD",
+    ] {
+        assert_eq!(
+            error(&format!("function {name} 0")),
+            format!("function name `{name}` is a predefined symbol of the assembler")
+        );
+        assert_eq!(
+            error(&format!("call {name} 0")),
+            format!("function name `{name}` is a predefined symbol of the assembler")
+        );
+    }
+    // Only exact matches
+    for name in ["R16", "Screen", "Main.SP", "KBD2"] {
+        assert!(parse(&format!("function {name} 0")).is_ok(), "{name}");
+    }
+}
+
+#[test]
+fn errors_point_at_the_function_name() {
+    let error = parse("  call   SCREEN 1").err().unwrap();
+    assert_eq!((error.column, error.text.as_str()), (9, "SCREEN"));
+}
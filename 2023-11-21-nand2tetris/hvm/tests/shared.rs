@@ -0,0 +1,173 @@
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+use emu::cpu::{Computer, Stop};
+use hvm::generator::HackGenerator;
+
+const SYS: &str = "\
+function Sys.init 0
+push constant 3000
+pop pointer 1
+push constant 12
+call Main.fib 1
+pop that 0
+push constant 5
+push constant 7
+call Main.max 2
+pop that 1
+push constant 32767
+push constant 1
+neg
+call Main.max 2
+pop that 2
+push constant 3
+push constant 3
+eq
+pop that 3
+label END
+goto END
+";
+
+const MAIN: &str = "\
+function Main.fib 0
+push argument 0
+push constant 2
+lt
+if-goto BASE
+push argument 0
+push constant 1
+sub
+call Main.fib 1
+push argument 0
+push constant 2
+sub
+call Main.fib 1
+add
+return
+label BASE
+push argument 0
+return
+function Main.max 0
+push argument 0
+push argument 1
+gt
+if-goto FIRST
+push argument 1
+return
+label FIRST
+push argument 0
+return
+";
+
+// A directory with `Sys.vm` and `Main.vm`
+fn setup(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("hvm-shared-{}-{name}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("Sys.vm"), SYS).unwrap();
+    fs::write(dir.join("Main.vm"), MAIN).unwrap();
+    dir
+}
+
+// Number of instructions, leaving out labels and comments
+fn count(code: &str) -> usize {
+    code.lines()
+        .filter(|line| !line.starts_with('(') && !line.starts_with("//") && !line.is_empty())
+        .count()
+}
+
+fn run(source: &str) -> Computer {
+    let mut computer = Computer::new(&asm::assemble(source).unwrap().words);
+    assert_eq!(computer.run(1_000_000), Stop::Halted);
+    computer
+}
+
+#[test]
+fn routines_assemble_on_their_own() {
+    let source = format!(
+        "{}\n{}\n(Sys.init)\n",
+        HackGenerator::bootstrap(),
+        HackGenerator::routines()
+    );
+    let program = asm::assemble(&source).unwrap();
+    for routine in ["$$call", "$$return", "$$eq", "$$gt", "$$lt"] {
+        assert!(program.symbols.contains_key(routine), "{routine}");
+    }
+}
+
+#[test]
+fn shared_programs_compute_as_inline_ones() {
+    let dir = setup("run");
+    let inline_source = common::translate(&dir, false);
+    let shared_source = common::translate(&dir, true);
+    let inline = run(&inline_source);
+    let shared = run(&shared_source);
+    fs::remove_dir_all(dir).unwrap();
+
+    assert_eq!(inline.ram()[3000..3004], [144, 7, 32767, 0xFFFF]);
+    // R13 to R15 are scratch registers of the generated code, and the stack holds return
+    // addresses
+    assert_eq!(shared.ram()[..13], inline.ram()[..13]);
+    assert_eq!(shared.ram()[16..256], inline.ram()[16..256]);
+    assert_eq!(shared.ram()[2048..16384], inline.ram()[2048..16384]);
+    assert!(count(&shared_source) < count(&inline_source));
+}
+
+#[test]
+fn shared_comparisons_do_not_overflow() {
+    let fixture = common::fixture("Compare");
+    let dir = std::env::temp_dir().join(format!("hvm-shared-compare-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    for file in ["Compare.tst", "Compare.cmp"] {
+        fs::copy(fixture.join(file), dir.join(file)).unwrap();
+    }
+    fs::write(dir.join("Compare.asm"), common::translate(&fixture, true)).unwrap();
+
+    let outcome = tst::run(&dir.join("Compare.tst")).unwrap();
+    assert!(outcome.mismatch.is_none(), "{}", outcome.output);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn size_reports_count_the_written_instructions() {
+    let dir = setup("size");
+    let output = Command::new(env!("CARGO_BIN_EXE_hvm"))
+        .args(["--shared", "--size"])
+        .arg(&dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let written =
+        fs::read_to_string(dir.join(dir.file_name().unwrap()).with_extension("asm")).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    let rows: Vec<(&str, usize, usize)> = stdout
+        .lines()
+        .skip(1)
+        .map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            (
+                fields[0],
+                fields[1].parse().unwrap(),
+                fields[2].parse().unwrap(),
+            )
+        })
+        .collect();
+    let names: Vec<&str> = rows.iter().map(|row| row.0).collect();
+    assert_eq!(
+        names,
+        ["call", "return", "compare", "routines", "other", "total"]
+    );
+
+    let routines = count(&HackGenerator::routines());
+    assert_eq!((rows[3].1, rows[3].2), (0, routines));
+    let (_, inline, shared) = rows[5];
+    assert_eq!(shared, count(&written));
+    assert_eq!(inline, rows[..5].iter().map(|row| row.1).sum::<usize>());
+    assert!(shared < inline);
+    // Shared calls and returns are shorter
+    assert!(rows[0].2 < rows[0].1 && rows[1].2 < rows[1].1);
+}
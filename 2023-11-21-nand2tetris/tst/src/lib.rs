@@ -0,0 +1,4 @@
+pub mod runner;
+pub mod script;
+
+pub use runner::{run, Mismatch, Outcome};
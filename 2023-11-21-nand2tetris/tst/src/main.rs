@@ -0,0 +1,42 @@
+use std::env;
+use std::path::Path;
+use std::process::ExitCode;
+
+use anyhow::{bail, Result};
+
+use tst::run;
+
+// Usage: tst file.tst...
+//
+// Every script is run and its output compared with its `compare-to` table.
+// The exit status is non-zero if any comparison fails.
+fn main() -> Result<ExitCode> {
+    let arguments: Vec<String> = env::args().collect();
+    if arguments.len() < 2 {
+        bail!(
+            "Usage: {} file.tst...",
+            arguments.first().unwrap_or(&"tst".to_string())
+        );
+    }
+
+    let mut failed = 0;
+    for file in &arguments[1..] {
+        let outcome = run(Path::new(file))?;
+        match outcome.mismatch {
+            None => println!("{file}: End of script - Comparison ended successfully"),
+            Some(mismatch) => {
+                failed += 1;
+                println!(
+                    "{file}: Comparison failure at line {}\n  expected: {}\n  actual:   {}",
+                    mismatch.line, mismatch.expected, mismatch.actual
+                );
+            }
+        }
+    }
+
+    Ok(if failed == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
@@ -0,0 +1,230 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use emu::loader::load;
+use emu::Computer;
+
+use crate::script::{parse, Column, Command, Compare, Condition, Var};
+
+/// Where the output table first disagrees with the `.cmp` file
+pub struct Mismatch {
+    /// 1-based line in the `.out` file
+    pub line: usize,
+    pub expected: String,
+    pub actual: String,
+}
+
+pub struct Outcome {
+    /// The produced `.out` table
+    pub output: String,
+    /// `None` when the script has no `compare-to` or the tables agree
+    pub mismatch: Option<Mismatch>,
+}
+
+struct Runner {
+    dir: PathBuf,
+    computer: Option<Computer>,
+    columns: Vec<Column>,
+    output: String,
+    output_file: Option<PathBuf>,
+    compare_to: Option<PathBuf>,
+}
+
+/// Run the `.tst` script at `path`. Files it names are relative to its directory.
+/// The `.out` table is written if the script has an `output-file`.
+pub fn run(path: &Path) -> Result<Outcome> {
+    let source =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let commands = parse(&source).with_context(|| format!("Failed to parse {}", path.display()))?;
+
+    let mut runner = Runner {
+        dir: path.parent().unwrap_or(Path::new(".")).to_path_buf(),
+        computer: None,
+        columns: Vec::new(),
+        output: String::new(),
+        output_file: None,
+        compare_to: None,
+    };
+    runner.execute(&commands)?;
+
+    if let Some(out) = &runner.output_file {
+        fs::write(out, &runner.output)
+            .with_context(|| format!("Failed to write {}", out.display()))?;
+    }
+
+    let mismatch = match &runner.compare_to {
+        Some(cmp) => {
+            let expected = fs::read_to_string(cmp)
+                .with_context(|| format!("Failed to read {}", cmp.display()))?;
+            compare(&expected, &runner.output)
+        }
+        None => None,
+    };
+
+    Ok(Outcome {
+        output: runner.output,
+        mismatch,
+    })
+}
+
+impl Runner {
+    fn computer(&mut self) -> Result<&mut Computer> {
+        self.computer
+            .as_mut()
+            .context("No program is loaded. Use `load` first")
+    }
+
+    fn execute(&mut self, commands: &[Command]) -> Result<()> {
+        for command in commands {
+            match command {
+                Command::Load(file) => {
+                    self.computer = Some(Computer::new(&load(&self.dir.join(file))?));
+                }
+                Command::OutputFile(file) => self.output_file = Some(self.dir.join(file)),
+                Command::CompareTo(file) => self.compare_to = Some(self.dir.join(file)),
+                Command::OutputList(columns) => {
+                    self.columns = columns.clone();
+                    self.output.push_str(&header(&self.columns));
+                }
+                Command::Set(var, value) => self.set(*var, *value)?,
+                Command::Repeat(Some(n), body) => {
+                    for _ in 0..*n {
+                        self.execute(body)?;
+                    }
+                }
+                Command::Repeat(None, body) => loop {
+                    if self.computer()?.is_halted() {
+                        break;
+                    }
+                    self.execute(body)?;
+                },
+                Command::While(condition, body) => {
+                    while self.holds(condition)? {
+                        self.execute(body)?;
+                    }
+                }
+                // A whole instruction happens on the falling edge
+                Command::Tick => {}
+                Command::Tock | Command::TickTock => self.computer()?.step(),
+                Command::Output => {
+                    let row = self.row()?;
+                    self.output.push_str(&row);
+                }
+                Command::Echo(s) => println!("{s}"),
+                Command::ClearEcho => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn get(&mut self, var: Var) -> Result<u16> {
+        let computer = self.computer()?;
+        Ok(match var {
+            Var::A => computer.a(),
+            Var::D => computer.d(),
+            Var::PC => computer.pc(),
+            Var::Ram(address) => computer.ram()[address],
+            Var::Time => computer.cycles() as u16,
+        })
+    }
+
+    fn set(&mut self, var: Var, value: u16) -> Result<()> {
+        let computer = self.computer()?;
+        match var {
+            Var::A => computer.set_a(value),
+            Var::D => computer.set_d(value),
+            Var::PC => computer.set_pc(value),
+            Var::Ram(address) => computer.ram_mut()[address] = value,
+            Var::Time => bail!("time cannot be set"),
+        }
+        Ok(())
+    }
+
+    fn holds(&mut self, condition: &Condition) -> Result<bool> {
+        let value = self.get(condition.var)? as i16;
+        Ok(match condition.compare {
+            Compare::Eq => value == condition.value,
+            Compare::Ne => value != condition.value,
+            Compare::Lt => value < condition.value,
+            Compare::Gt => value > condition.value,
+            Compare::Le => value <= condition.value,
+            Compare::Ge => value >= condition.value,
+        })
+    }
+
+    fn row(&mut self) -> Result<String> {
+        let mut row = String::from("|");
+        for column in self.columns.clone() {
+            let width = column.width;
+            let value = self.get(column.var)?;
+            let text = match column.format {
+                'D' | 'S' => (value as i16).to_string(),
+                'X' => format!("{value:0width$X}"),
+                _ => format!("{value:0width$b}"),
+            };
+            // Keep the low digits of values that do not fit
+            let text = &text[text.len().saturating_sub(width)..];
+
+            row.push_str(&" ".repeat(column.left));
+            match column.format {
+                'S' => row.push_str(&format!("{text:<width$}")),
+                _ => row.push_str(&format!("{text:>width$}")),
+            }
+            row.push_str(&" ".repeat(column.right));
+            row.push('|');
+        }
+        row.push('\n');
+        Ok(row)
+    }
+}
+
+// Column names centered in their cells, truncated when the cell is too narrow
+fn header(columns: &[Column]) -> String {
+    let mut header = String::from("|");
+    for column in columns {
+        let total = column.left + column.width + column.right;
+        let name: String = column.var.to_string().chars().take(total).collect();
+        let left = (total - name.len()) / 2;
+        header.push_str(&" ".repeat(left));
+        header.push_str(&name);
+        header.push_str(&" ".repeat(total - left - name.len()));
+        header.push('|');
+    }
+    header.push('\n');
+    header
+}
+
+// Cells are compared with surrounding spaces removed, and a cell of `*` in the
+// expected table matches anything
+fn compare(expected: &str, actual: &str) -> Option<Mismatch> {
+    let cells = |line: &str| -> Vec<String> {
+        line.trim()
+            .split('|')
+            .map(|cell| cell.trim().to_string())
+            .collect()
+    };
+
+    let expected_lines: Vec<&str> = expected.lines().filter(|l| !l.trim().is_empty()).collect();
+    let actual_lines: Vec<&str> = actual.lines().collect();
+
+    for i in 0..expected_lines.len().max(actual_lines.len()) {
+        let e = expected_lines.get(i).copied().unwrap_or_default();
+        let a = actual_lines.get(i).copied().unwrap_or_default();
+        let (ec, ac) = (cells(e), cells(a));
+        let matches = ec.len() == ac.len()
+            && ec
+                .iter()
+                .zip(&ac)
+                .all(|(e, a)| e == a || (!e.is_empty() && e.chars().all(|c| c == '*')));
+        if !matches {
+            return Some(Mismatch {
+                line: i + 1,
+                expected: e.to_string(),
+                actual: a.to_string(),
+            });
+        }
+    }
+
+    None
+}
@@ -0,0 +1,358 @@
+use std::fmt;
+
+use anyhow::{bail, Context, Result};
+
+/// A value the CPU emulator can read or set
+#[derive(Clone, Copy)]
+pub enum Var {
+    A,
+    D,
+    PC,
+    Ram(usize),
+    Time,
+}
+
+impl fmt::Display for Var {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Var::A => write!(f, "A"),
+            Var::D => write!(f, "D"),
+            Var::PC => write!(f, "PC"),
+            Var::Ram(address) => write!(f, "RAM[{address}]"),
+            Var::Time => write!(f, "time"),
+        }
+    }
+}
+
+/// One column of `output-list`, e.g. `RAM[0]%D2.6.2`
+#[derive(Clone)]
+pub struct Column {
+    pub var: Var,
+    pub format: char,
+    pub left: usize,
+    pub width: usize,
+    pub right: usize,
+}
+
+#[derive(Clone, Copy)]
+pub enum Compare {
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+}
+
+pub struct Condition {
+    pub var: Var,
+    pub compare: Compare,
+    pub value: i16,
+}
+
+pub enum Command {
+    Load(String),
+    OutputFile(String),
+    CompareTo(String),
+    OutputList(Vec<Column>),
+    Set(Var, u16),
+    /// `None` repeats forever, which only makes sense for programs that halt
+    Repeat(Option<u64>, Vec<Command>),
+    While(Condition, Vec<Command>),
+    Tick,
+    Tock,
+    TickTock,
+    Output,
+    Echo(String),
+    ClearEcho,
+}
+
+#[derive(PartialEq)]
+enum Token {
+    Word(String),
+    Str(String),
+    Open,
+    Close,
+    // `,` `;` and `!` all end a command
+    End,
+}
+
+struct Tokens {
+    tokens: Vec<(usize, Token)>,
+    next: usize,
+}
+
+impl Tokens {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next).map(|(_, t)| t)
+    }
+
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.next.min(self.tokens.len().saturating_sub(1)))
+            .map_or(0, |(line, _)| *line)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self
+            .tokens
+            .get_mut(self.next)
+            .map(|(_, t)| std::mem::replace(t, Token::End));
+        self.next += 1;
+        token
+    }
+
+    fn word(&mut self) -> Result<String> {
+        let line = self.line();
+        match self.next() {
+            Some(Token::Word(w)) => Ok(w),
+            _ => bail!("Line {line}: expect a word"),
+        }
+    }
+
+    fn words_until_end(&mut self) -> Vec<String> {
+        let mut words = Vec::new();
+        while let Some(Token::Word(_)) = self.peek() {
+            words.push(self.word().unwrap());
+        }
+        words
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '\n' => line += 1,
+            _ if c.is_whitespace() => {}
+            '/' if chars.get(i + 1) == Some(&'/') => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+                continue;
+            }
+            '/' if chars.get(i + 1) == Some(&'*') => {
+                i += 2;
+                while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                    if chars[i] == '\n' {
+                        line += 1;
+                    }
+                    i += 1;
+                }
+                i += 1;
+            }
+            '{' => tokens.push((line, Token::Open)),
+            '}' => tokens.push((line, Token::Close)),
+            ',' | ';' | '!' => tokens.push((line, Token::End)),
+            '"' => {
+                let start = i + 1;
+                i = start;
+                while i < chars.len() && chars[i] != '"' {
+                    i += 1;
+                }
+                if i == chars.len() {
+                    bail!("Line {line}: unterminated string");
+                }
+                tokens.push((line, Token::Str(chars[start..i].iter().collect())));
+            }
+            _ => {
+                let start = i;
+                while i < chars.len()
+                    && !chars[i].is_whitespace()
+                    && !matches!(chars[i], '{' | '}' | ',' | ';' | '!' | '"')
+                {
+                    i += 1;
+                }
+                tokens.push((line, Token::Word(chars[start..i].iter().collect())));
+                continue;
+            }
+        }
+        i += 1;
+    }
+
+    Ok(tokens)
+}
+
+pub fn parse(source: &str) -> Result<Vec<Command>> {
+    let mut tokens = Tokens {
+        tokens: tokenize(source)?,
+        next: 0,
+    };
+    let commands = parse_block(&mut tokens)?;
+    if tokens.peek().is_some() {
+        bail!("Line {}: unexpected `}}`", tokens.line());
+    }
+    Ok(commands)
+}
+
+fn parse_block(tokens: &mut Tokens) -> Result<Vec<Command>> {
+    let mut commands = Vec::new();
+
+    loop {
+        match tokens.peek() {
+            None | Some(Token::Close) => return Ok(commands),
+            Some(Token::End) => {
+                tokens.next();
+                continue;
+            }
+            _ => {}
+        }
+
+        let line = tokens.line();
+        let command = tokens.word()?;
+        let command = match command.as_str() {
+            "load" => Command::Load(tokens.word()?),
+            "output-file" => Command::OutputFile(tokens.word()?),
+            "compare-to" => Command::CompareTo(tokens.word()?),
+            "output-list" => Command::OutputList(
+                tokens
+                    .words_until_end()
+                    .iter()
+                    .map(|w| parse_column(w))
+                    .collect::<Result<_>>()
+                    .with_context(|| format!("Line {line}"))?,
+            ),
+            "set" => {
+                let var = parse_var(&tokens.word()?).with_context(|| format!("Line {line}"))?;
+                let value = parse_value(&tokens.word()?).with_context(|| format!("Line {line}"))?;
+                Command::Set(var, value as u16)
+            }
+            "repeat" => {
+                let count = match tokens.peek() {
+                    Some(Token::Word(_)) => {
+                        let n = tokens.word()?;
+                        Some(
+                            n.parse()
+                                .with_context(|| format!("Line {line}: invalid count {n}"))?,
+                        )
+                    }
+                    _ => None,
+                };
+                Command::Repeat(count, parse_body(tokens, line)?)
+            }
+            "while" => {
+                let words = [tokens.word()?, tokens.word()?, tokens.word()?];
+                let condition = parse_condition(&words).with_context(|| format!("Line {line}"))?;
+                Command::While(condition, parse_body(tokens, line)?)
+            }
+            "tick" => Command::Tick,
+            "tock" => Command::Tock,
+            "ticktock" => Command::TickTock,
+            "output" => Command::Output,
+            "echo" => match tokens.next() {
+                Some(Token::Str(s)) | Some(Token::Word(s)) => Command::Echo(s),
+                _ => bail!("Line {line}: echo needs a string"),
+            },
+            "clear-echo" => Command::ClearEcho,
+            // Breakpoints only matter in the GUI
+            "breakpoint" | "clear-breakpoints" => {
+                tokens.words_until_end();
+                continue;
+            }
+            other => bail!("Line {line}: unknown command {other}"),
+        };
+        commands.push(command);
+    }
+}
+
+fn parse_body(tokens: &mut Tokens, line: usize) -> Result<Vec<Command>> {
+    if tokens.next() != Some(Token::Open) {
+        bail!("Line {line}: expect `{{`");
+    }
+    let body = parse_block(tokens)?;
+    if tokens.next() != Some(Token::Close) {
+        bail!("Line {line}: missing `}}`");
+    }
+    Ok(body)
+}
+
+fn parse_var(s: &str) -> Result<Var> {
+    Ok(match s {
+        "A" => Var::A,
+        "D" => Var::D,
+        "PC" => Var::PC,
+        "time" => Var::Time,
+        _ => {
+            let address = s
+                .strip_prefix("RAM[")
+                .and_then(|s| s.strip_suffix(']'))
+                .with_context(|| format!("unknown variable {s}"))?;
+            let address = address
+                .parse::<usize>()
+                .ok()
+                .filter(|a| *a < emu::cpu::RAM_SIZE)
+                .with_context(|| format!("invalid RAM address {address}"))?;
+            Var::Ram(address)
+        }
+    })
+}
+
+/// Values are decimal unless prefixed with `%X`, `%B` or `%D`
+fn parse_value(s: &str) -> Result<i16> {
+    let value = if let Some(hex) = s.strip_prefix("%X") {
+        i32::from_str_radix(hex, 16)
+    } else if let Some(bin) = s.strip_prefix("%B") {
+        i32::from_str_radix(bin, 2)
+    } else {
+        s.strip_prefix("%D").unwrap_or(s).parse::<i32>()
+    }
+    .with_context(|| format!("invalid value {s}"))?;
+
+    if !(-32768..=65535).contains(&value) {
+        bail!("value {s} does not fit in 16 bits");
+    }
+    Ok(value as i16)
+}
+
+fn parse_column(s: &str) -> Result<Column> {
+    let Some((var, format)) = s.split_once('%') else {
+        return Ok(Column {
+            var: parse_var(s)?,
+            format: 'B',
+            left: 1,
+            width: 16,
+            right: 1,
+        });
+    };
+
+    let mut chars = format.chars();
+    let kind = chars.next().filter(|c| "BDXS".contains(*c));
+    let widths: Vec<usize> = chars
+        .as_str()
+        .split('.')
+        .map(str::parse)
+        .collect::<Result<_, _>>()
+        .unwrap_or_default();
+    match (kind, widths.as_slice()) {
+        (Some(format), [left, width, right]) => Ok(Column {
+            var: parse_var(var)?,
+            format,
+            left: *left,
+            width: *width,
+            right: *right,
+        }),
+        _ => bail!("invalid output format {s}"),
+    }
+}
+
+fn parse_condition(words: &[String; 3]) -> Result<Condition> {
+    let compare = match words[1].as_str() {
+        "=" => Compare::Eq,
+        "<>" => Compare::Ne,
+        "<" => Compare::Lt,
+        ">" => Compare::Gt,
+        "<=" => Compare::Le,
+        ">=" => Compare::Ge,
+        other => bail!("unknown comparison {other}"),
+    };
+    Ok(Condition {
+        var: parse_var(&words[0])?,
+        compare,
+        value: parse_value(&words[2])?,
+    })
+}
@@ -0,0 +1,59 @@
+use std::fs;
+use std::path::PathBuf;
+
+use tst::run;
+
+const ADD: &str = "@2\nD=A\n@3\nD=D+A\n@0\nM=D\n(END)\n@END\n0;JMP\n";
+
+const SCRIPT: &str = "load Add.asm,
+output-file Add.out,
+compare-to Add.cmp,
+output-list RAM[0]%D2.6.2 time%D1.4.1;
+set RAM[0] -1,
+output;
+repeat 6 { ticktock; }
+output;
+";
+
+// A directory with `Add.asm`, `Add.tst` and `cmp` as `Add.cmp`
+fn setup(name: &str, cmp: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("tst-{}-{name}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("Add.asm"), ADD).unwrap();
+    fs::write(dir.join("Add.tst"), SCRIPT).unwrap();
+    fs::write(dir.join("Add.cmp"), cmp).unwrap();
+    dir
+}
+
+const EXPECTED: &str = "\
+|  RAM[0]  | time |
+|      -1  |    0 |
+|       5  |    6 |
+";
+
+#[test]
+fn output_matches_the_cmp_file() {
+    let dir = setup("match", EXPECTED);
+    let outcome = run(&dir.join("Add.tst")).unwrap();
+    assert_eq!(outcome.output, EXPECTED);
+    assert!(outcome.mismatch.is_none());
+    assert_eq!(fs::read_to_string(dir.join("Add.out")).unwrap(), EXPECTED);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn stars_match_any_value_and_spaces_are_ignored() {
+    let dir = setup("stars", "|RAM[0]|time|\n|-1|0|\n|   5 | ***** |\n");
+    assert!(run(&dir.join("Add.tst")).unwrap().mismatch.is_none());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn the_first_differing_line_is_reported() {
+    let dir = setup("mismatch", &EXPECTED.replace("   5  |", "   6  |"));
+    let mismatch = run(&dir.join("Add.tst")).unwrap().mismatch.unwrap();
+    assert_eq!(mismatch.line, 3);
+    assert_eq!(mismatch.expected, "|       6  |    6 |");
+    assert_eq!(mismatch.actual, "|       5  |    6 |");
+    fs::remove_dir_all(dir).unwrap();
+}
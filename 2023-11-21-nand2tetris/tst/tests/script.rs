@@ -0,0 +1,76 @@
+use tst::script::{parse, Command, Compare, Var};
+
+#[test]
+fn scripts_parse_into_commands() {
+    let commands = parse(
+        "// Adds 2 and 3
+load Add.hack,
+output-file Add.out,
+compare-to Add.cmp,
+output-list RAM[0]%D2.6.2 A%X1.4.1;
+set RAM[0] %X10, set D -1;
+repeat 6 { ticktock; }
+while PC < 10 { tick, tock; }
+echo \"done\";
+output;
+",
+    )
+    .unwrap();
+
+    assert!(matches!(&commands[0], Command::Load(f) if f == "Add.hack"));
+    assert!(matches!(&commands[1], Command::OutputFile(f) if f == "Add.out"));
+    assert!(matches!(&commands[2], Command::CompareTo(f) if f == "Add.cmp"));
+    let Command::OutputList(columns) = &commands[3] else {
+        panic!("expect output-list");
+    };
+    let shape: Vec<(String, char, usize, usize, usize)> = columns
+        .iter()
+        .map(|c| (c.var.to_string(), c.format, c.left, c.width, c.right))
+        .collect();
+    assert_eq!(
+        shape,
+        [
+            ("RAM[0]".to_string(), 'D', 2, 6, 2),
+            ("A".to_string(), 'X', 1, 4, 1)
+        ]
+    );
+    assert!(matches!(commands[4], Command::Set(Var::Ram(0), 16)));
+    assert!(matches!(commands[5], Command::Set(Var::D, 0xFFFF)));
+    assert!(matches!(
+        &commands[6],
+        Command::Repeat(Some(6), body) if matches!(body[..], [Command::TickTock])
+    ));
+    let Command::While(condition, body) = &commands[7] else {
+        panic!("expect while");
+    };
+    assert!(matches!(condition.var, Var::PC));
+    assert!(matches!(condition.compare, Compare::Lt));
+    assert_eq!(condition.value, 10);
+    assert!(matches!(body[..], [Command::Tick, Command::Tock]));
+    assert!(matches!(&commands[8], Command::Echo(s) if s == "done"));
+    assert!(matches!(commands[9], Command::Output));
+    assert_eq!(commands.len(), 10);
+}
+
+#[test]
+fn repeat_without_a_count_runs_until_halt() {
+    let commands = parse("repeat { ticktock; }").unwrap();
+    assert!(matches!(commands[..], [Command::Repeat(None, _)]));
+}
+
+#[test]
+fn malformed_scripts_report_their_line() {
+    for (source, message) in [
+        (
+            "load A.hack;\nfrobnicate;",
+            "Line 2: unknown command frobnicate",
+        ),
+        ("repeat 3 { ticktock;", "Line 1: missing `}`"),
+        ("set RAM[0] 70000;", "does not fit in 16 bits"),
+        ("output-list RAM[0]%Q1.6.1;", "invalid output format"),
+        ("echo \"x;", "unterminated string"),
+    ] {
+        let error = format!("{:#}", parse(source).err().unwrap());
+        assert!(error.contains(message), "{source}: {error}");
+    }
+}